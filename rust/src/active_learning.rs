@@ -0,0 +1,50 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Chooses which samples an analyst should label next. Their verdicts are fed back with `Forest::add_feedback`.
+
+use crate::isolation_forest::{Forest, Sample};
+
+/// How to rank candidate samples for labeling.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum QueryStrategy {
+    #[default]
+    MostAnomalous, // The highest scores first, which Active Anomaly Discovery found to surface the most true anomalies
+    NearThreshold, // The scores closest to the forest's threshold, or to 0.5 without one, where the forest is least sure
+    MostUncertain, // The largest disagreement between the trees, measured by the standard deviation of their scores
+}
+
+/// Returns the indices of up to the given number of candidates to label next, most informative first.
+pub fn query(forest: &Forest, candidates: &[Sample], num_queries: usize, strategy: QueryStrategy) -> Vec<usize> {
+    let threshold = forest.threshold().unwrap_or(0.5);
+    let priorities: Vec<f64> = candidates.iter().map(|sample| match strategy {
+        QueryStrategy::MostAnomalous => forest.normalized_score(sample),
+        QueryStrategy::NearThreshold => -(forest.normalized_score(sample) - threshold).abs(),
+        QueryStrategy::MostUncertain => forest.score_with_uncertainty(sample, 0.95).std,
+    }).collect();
+
+    let mut indices: Vec<usize> = (0..candidates.len()).collect();
+    indices.sort_by(|&a, &b| priorities[b].total_cmp(&priorities[a]));
+    indices.truncate(num_queries);
+    indices
+}
@@ -0,0 +1,78 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Dense matrix training and scoring for `Forest`, using `ndarray`.
+
+extern crate ndarray;
+
+use self::ndarray::{Array1, ArrayView1, ArrayView2};
+use std::collections::HashMap;
+use crate::isolation_forest::{Forest, ForestError};
+
+impl Forest {
+    /// Trains the forest on a dense matrix, with one row per sample and one column per feature, replacing any
+    /// existing training data and trees. NaN values are treated as missing.
+    pub fn fit_array(&mut self, data: ArrayView2<f64>, column_names: &[&str]) -> Result<(), ForestError> {
+        if column_names.len() != data.ncols() {
+            return Err(ForestError::ColumnCountMismatch { expected: data.ncols(), found: column_names.len() });
+        }
+
+        let mut columns = HashMap::new();
+        for (column, column_name) in data.columns().into_iter().zip(column_names) {
+            columns.insert(column_name.to_string(), column.to_vec());
+        }
+
+        let column_names = column_names.iter().map(|column_name| column_name.to_string()).collect();
+        self.set_columns(columns, column_names);
+        self.create();
+        Ok(())
+    }
+
+    /// Scores each row of the matrix against the entire forest of trees. Results are the average path lengths.
+    /// Returns an error if the number of columns differs from the number the forest was trained with.
+    pub fn score_array(&self, data: ArrayView2<f64>) -> Result<Array1<f64>, ForestError> {
+        self.map_rows(data, |feature_value| self.score_with(feature_value))
+    }
+
+    /// Scores each row of the matrix against the entire forest of trees. Results are normalized so that values
+    /// close to 1 indicate anomalies and values close to zero indicate normal values. Returns an error if the number
+    /// of columns differs from the number the forest was trained with.
+    pub fn normalized_score_array(&self, data: ArrayView2<f64>) -> Result<Array1<f64>, ForestError> {
+        self.map_rows(data, |feature_value| self.normalized_score_with(feature_value))
+    }
+
+    fn map_rows<S>(&self, data: ArrayView2<f64>, score: S) -> Result<Array1<f64>, ForestError>
+    where
+        S: Fn(&dyn Fn(&str) -> Option<f64>) -> f64,
+    {
+        let column_names = self.column_names();
+        if data.ncols() != column_names.len() {
+            return Err(ForestError::ColumnCountMismatch { expected: column_names.len(), found: data.ncols() });
+        }
+
+        let column_indexes: HashMap<String, usize> = column_names.into_iter().enumerate().map(|(i, name)| (name, i)).collect();
+        Ok(data.rows().into_iter().map(|row: ArrayView1<f64>| {
+            score(&|feature_name: &str| column_indexes.get(feature_name).map(|&i| row[i]).filter(|value| !value.is_nan()))
+        }).collect())
+    }
+}
@@ -0,0 +1,65 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Command line tools for forests saved by `Forest::dump`.
+//!
+//! Usage: isoforest score <model file>
+//!
+//! Reads newline-delimited JSON samples from stdin and writes them to stdout with their scores added.
+
+extern crate isolation_forest;
+
+use isolation_forest::isolation_forest::Forest;
+use isolation_forest::jsonl::score_json_lines;
+use std::io::{stdin, stdout};
+use std::process::exit;
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} score <model file>", program);
+    exit(1);
+}
+
+fn score(model_path: &str) {
+    let json_str = std::fs::read_to_string(model_path).unwrap_or_else(|e| {
+        eprintln!("Couldn't read {}: {}", model_path, e);
+        exit(1);
+    });
+    let forest = Forest::load(&json_str).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}: {}", model_path, e);
+        exit(1);
+    });
+
+    if let Err(e) = score_json_lines(&forest, stdin().lock(), stdout().lock()) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("score") if args.len() == 3 => score(&args[2]),
+        _ => usage(&args[0]),
+    }
+}
@@ -0,0 +1,202 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! A local HTTP server that scores samples against a forest saved by `Forest::dump`.
+//!
+//! Usage: server <model file> [--address <host:port>] [--reload-interval <seconds>]
+//!
+//! Endpoints:
+//!   POST /score   Scores a sample, or an array of samples, in the same JSON shape as a serialized `Sample`.
+//!   GET  /health  Reports whether the server is up.
+//!   GET  /model   Describes the loaded model.
+//!
+//! The model file is reloaded whenever it changes.
+
+extern crate isolation_forest;
+extern crate serde_json;
+extern crate tiny_http;
+
+use isolation_forest::isolation_forest::{Forest, Sample};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// The loaded forest, along with where and when it was loaded from.
+struct Model {
+    forest: Forest,
+    path: PathBuf,
+    modified: Option<SystemTime>, // Modification time of the model file when it was loaded
+    loaded: u64, // When the model was loaded, in seconds since the Unix epoch
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn load_model(path: &Path) -> Result<Model, String> {
+    let modified = modified_time(path);
+    let json_str = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    let forest = Forest::load(&json_str).map_err(|e| format!("Couldn't load {}: {}", path.display(), e))?;
+    let loaded = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    Ok(Model { forest, path: path.to_path_buf(), modified, loaded })
+}
+
+/// Reloads the model whenever the model file's modification time changes. If the new file can't be loaded,
+/// the previous model stays in service.
+fn watch_model(model: Arc<RwLock<Model>>, reload_interval: Duration) {
+    loop {
+        std::thread::sleep(reload_interval);
+
+        let (path, modified) = {
+            let model = model.read().unwrap();
+            (model.path.clone(), model.modified)
+        };
+        if modified_time(&path) == modified {
+            continue;
+        }
+
+        match load_model(&path) {
+            Ok(new_model) => {
+                *model.write().unwrap() = new_model;
+                eprintln!("Reloaded {}", path.display());
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+            }
+        }
+    }
+}
+
+fn score_sample(forest: &Forest, sample: &Sample) -> Value {
+    json!({
+        "name": sample.name,
+        "score": forest.score(sample),
+        "normalized_score": forest.normalized_score(sample),
+    })
+}
+
+/// Scores the request body, which is either a single sample or an array of samples.
+fn score(forest: &Forest, body: &str) -> Result<Value, String> {
+    let value: Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    if value.is_array() {
+        let samples: Vec<Sample> = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        Ok(Value::Array(samples.iter().map(|sample| score_sample(forest, sample)).collect()))
+    }
+    else {
+        let sample: Sample = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        Ok(score_sample(forest, &sample))
+    }
+}
+
+fn describe(model: &Model) -> Value {
+    json!({
+        "path": model.path.display().to_string(),
+        "loaded": model.loaded,
+        "num_trees": model.forest.num_trees(),
+        "feature_names": model.forest.feature_names(),
+        "missing_value_policy": model.forest.missing_value_policy(),
+        "aggregator": model.forest.aggregator(),
+        "stats": model.forest.stats(),
+    })
+}
+
+fn handle(request: &mut Request, model: &RwLock<Model>) -> (u16, Value) {
+    match (request.method(), request.url()) {
+        (Method::Get, "/health") => {
+            (200, json!({ "status": "ok" }))
+        }
+        (Method::Get, "/model") => {
+            (200, describe(&model.read().unwrap()))
+        }
+        (Method::Post, "/score") => {
+            let mut body = String::new();
+            if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                return (400, json!({ "error": e.to_string() }));
+            }
+            match score(&model.read().unwrap().forest, &body) {
+                Ok(scores) => (200, scores),
+                Err(e) => (400, json!({ "error": e })),
+            }
+        }
+        _ => {
+            (404, json!({ "error": "Not found" }))
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut model_path = None;
+    let mut address = "127.0.0.1:8080".to_string();
+    let mut reload_interval = 2;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--address" if i + 1 < args.len() => {
+                address = args[i + 1].clone();
+                i += 1;
+            }
+            "--reload-interval" if i + 1 < args.len() => {
+                reload_interval = args[i + 1].parse().expect("The reload interval must be a number of seconds");
+                i += 1;
+            }
+            arg => {
+                model_path = Some(PathBuf::from(arg));
+            }
+        }
+        i += 1;
+    }
+
+    let model_path = match model_path {
+        Some(model_path) => model_path,
+        None => {
+            eprintln!("Usage: {} <model file> [--address <host:port>] [--reload-interval <seconds>]", args[0]);
+            std::process::exit(1);
+        }
+    };
+    let model = match load_model(&model_path) {
+        Ok(model) => Arc::new(RwLock::new(model)),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let watched_model = model.clone();
+    std::thread::spawn(move || watch_model(watched_model, Duration::from_secs(reload_interval)));
+
+    let server = Server::http(&address).unwrap_or_else(|e| panic!("Couldn't listen on {}: {}", address, e));
+    eprintln!("Listening on {}", address);
+
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    for mut request in server.incoming_requests() {
+        let (status_code, body) = handle(&mut request, &model);
+        let response = Response::from_string(body.to_string()).with_status_code(status_code).with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            eprintln!("Couldn't send a response: {}", e);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! A builder for `Forest`, with named options that are validated when the forest is built.
+
+extern crate serde;
+
+use self::serde::{Serialize, Deserialize};
+use crate::isolation_forest::{Forest, ForestError, MaxSamples, MissingValuePolicy};
+
+/// Configures a `Forest`. The defaults follow the original paper: 100 trees, each built from 256 samples and
+/// limited to a depth of ceil(log2(256)) = 8.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForestBuilder {
+    num_trees: u32,
+    max_samples: MaxSamples,
+    max_depth: Option<u32>, // None to derive the depth limit from max_samples
+    max_features: Option<usize>, // None to let every tree use every feature
+    bootstrap: bool,
+    seed: Option<u64>,
+    contamination: Option<f64>,
+    missing_value_policy: MissingValuePolicy,
+}
+
+impl Default for ForestBuilder {
+    fn default() -> ForestBuilder {
+        ForestBuilder {
+            num_trees: 100,
+            max_samples: MaxSamples::Count(256),
+            max_depth: None,
+            max_features: None,
+            bootstrap: false,
+            seed: None,
+            contamination: None,
+            missing_value_policy: MissingValuePolicy::default(),
+        }
+    }
+}
+
+impl ForestBuilder {
+    pub fn new() -> ForestBuilder {
+        ForestBuilder::default()
+    }
+
+    /// Sets the number of trees to build.
+    pub fn num_trees(mut self, num_trees: u32) -> ForestBuilder {
+        self.num_trees = num_trees;
+        self
+    }
+
+    /// Sets how many of the training samples each tree is built from.
+    pub fn max_samples(mut self, max_samples: MaxSamples) -> ForestBuilder {
+        self.max_samples = max_samples;
+        self
+    }
+
+    /// Sets the maximum depth of each tree, where zero means no limit. If not set, the limit is ceil(log2(n)) when
+    /// max_samples is a count of n samples, and there is no limit when it is a fraction.
+    pub fn max_depth(mut self, max_depth: u32) -> ForestBuilder {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets how many randomly selected features each tree may split on. Each tree draws its own features, which
+    /// helps when many of the features are irrelevant. If there are fewer features, trees use all of them.
+    pub fn max_features(mut self, max_features: usize) -> ForestBuilder {
+        self.max_features = Some(max_features);
+        self
+    }
+
+    /// Sets whether each tree's samples are drawn with replacement.
+    pub fn bootstrap(mut self, bootstrap: bool) -> ForestBuilder {
+        self.bootstrap = bootstrap;
+        self
+    }
+
+    /// Seeds the random number generator, so that the same training samples always produce the same trees.
+    pub fn seed(mut self, seed: u64) -> ForestBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the expected fraction of anomalies in the training data, which sets the forest's threshold.
+    pub fn contamination(mut self, contamination: f64) -> ForestBuilder {
+        self.contamination = Some(contamination);
+        self
+    }
+
+    /// Sets how samples that are missing a feature are scored.
+    pub fn missing_value_policy(mut self, missing_value_policy: MissingValuePolicy) -> ForestBuilder {
+        self.missing_value_policy = missing_value_policy;
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.num_trees == 0 {
+            return Err("num_trees must be at least 1".to_string());
+        }
+        match self.max_samples {
+            MaxSamples::Count(count) if count < 2 => {
+                return Err(format!("max_samples must be at least 2, got {}", count));
+            }
+            MaxSamples::Fraction(fraction) if !(fraction > 0.0 && fraction <= 1.0) => {
+                return Err(format!("max_samples must be a fraction greater than 0 and at most 1, got {}", fraction));
+            }
+            _ => {}
+        }
+        if let (Some(max_depth), MaxSamples::Count(count)) = (self.max_depth, self.max_samples) {
+            if max_depth as usize >= count {
+                return Err(format!("max_depth of {} can never be reached with max_samples of {}", max_depth, count));
+            }
+        }
+        if self.max_features == Some(0) {
+            return Err("max_features must be at least 1".to_string());
+        }
+        if let Some(contamination) = self.contamination {
+            if !(contamination > 0.0 && contamination <= 0.5) {
+                return Err(format!("contamination must be greater than 0 and at most 0.5, got {}", contamination));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the options and creates the forest, which is ready for samples to be added.
+    pub fn build(self) -> Result<Forest, ForestError> {
+        self.validate().map_err(ForestError::InvalidParameter)?;
+
+        let max_depth = match (self.max_depth, self.max_samples) {
+            (Some(max_depth), _) => max_depth,
+            (None, MaxSamples::Count(count)) => (count as f64).log2().ceil() as u32,
+            (None, MaxSamples::Fraction(_)) => 0,
+        };
+
+        let mut forest = Forest::new(self.num_trees, max_depth);
+        forest.set_sampling(self.max_samples, self.bootstrap);
+        forest.set_missing_value_policy(self.missing_value_policy);
+        if let Some(max_features) = self.max_features {
+            forest.set_max_features(max_features);
+        }
+        if let Some(seed) = self.seed {
+            forest.set_seed(seed);
+        }
+        if let Some(contamination) = self.contamination {
+            forest.set_contamination(contamination);
+        }
+        Ok(forest)
+    }
+}
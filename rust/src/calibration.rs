@@ -0,0 +1,202 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Maps normalized scores to calibrated outlier probabilities, so that the same probability means the same thing
+//! on every dataset. A calibration is either fit without labels to the tail of the training scores, or fit to a
+//! few labeled scores with Platt scaling or isotonic regression.
+
+extern crate serde;
+
+use self::serde::{Serialize, Deserialize};
+use crate::isolation_forest::{Float64Vec, ForestError};
+
+const MAX_NEWTON_ITERATIONS: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Calibration {
+    /// A generalized Pareto distribution fit to the training scores above a high threshold. Scores at or below the
+    /// threshold have zero probability, and the probability rises to one through the tail, following the fitted
+    /// distribution of the excess over the threshold.
+    Tail {
+        threshold: f64,        // Score above which the generalized Pareto distribution is used
+        tail_fraction: f64,    // Fraction of the training scores above the threshold
+        scale: f64,
+        shape: f64,
+    },
+    /// A logistic function of the score.
+    Platt {
+        slope: f64,
+        intercept: f64,
+    },
+    /// A non-decreasing, piecewise linear function of the score.
+    Isotonic {
+        scores: Float64Vec,
+        probabilities: Float64Vec,
+    },
+}
+
+impl Calibration {
+    /// Fits the unsupervised tail model to scores of the training data. The tail fraction, in (0, 0.5], is the
+    /// fraction of the highest scores fit by the generalized Pareto distribution.
+    pub fn tail(scores: &[f64], tail_fraction: f64) -> Result<Calibration, ForestError> {
+        if !(tail_fraction > 0.0 && tail_fraction <= 0.5) {
+            return Err(ForestError::InvalidParameter(format!("tail fraction must be in (0, 0.5], got {}", tail_fraction)));
+        }
+        let mut sorted = scores.to_vec();
+        sorted.sort_unstable_by(f64::total_cmp);
+        let num_excesses = (sorted.len() as f64 * tail_fraction).floor() as usize;
+        if num_excesses < 2 {
+            return Err(ForestError::InvalidParameter("too few scores to fit the tail".to_string()));
+        }
+
+        let threshold = quantile(&sorted, 1.0 - tail_fraction);
+        let excesses: Float64Vec = sorted.iter().filter(|score| **score > threshold).map(|score| score - threshold).collect();
+
+        // Method of moments estimates of the generalized Pareto distribution.
+        let mean = excesses.iter().sum::<f64>() / excesses.len().max(1) as f64;
+        let variance = excesses.iter().map(|excess| (excess - mean).powi(2)).sum::<f64>() / excesses.len().max(1) as f64;
+        let (scale, shape) = if variance > 0.0 {
+            let ratio = mean * mean / variance;
+            (0.5 * mean * (ratio + 1.0), 0.5 * (1.0 - ratio))
+        } else {
+            (mean.max(f64::MIN_POSITIVE), 0.0)
+        };
+        Ok(Calibration::Tail { threshold, tail_fraction, scale, shape })
+    }
+
+    /// Fits a logistic function to labeled scores, where true labels are anomalies, using Platt's smoothed targets.
+    pub fn platt(scores: &[f64], labels: &[bool]) -> Result<Calibration, ForestError> {
+        check_labels(scores, labels)?;
+        let num_positive = labels.iter().filter(|label| **label).count() as f64;
+        let num_negative = labels.len() as f64 - num_positive;
+        let high_target = (num_positive + 1.0) / (num_positive + 2.0);
+        let low_target = 1.0 / (num_negative + 2.0);
+
+        // Newton's method on the log loss, with a little damping to keep separable data finite.
+        let (mut slope, mut intercept) = (0.0, ((num_positive + 1.0) / (num_negative + 1.0)).ln());
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let (mut g_slope, mut g_intercept) = (0.0, 0.0);
+            let (mut h_ss, mut h_si, mut h_ii) = (1e-6, 0.0, 1e-6);
+            for (score, label) in scores.iter().zip(labels) {
+                let target = if *label { high_target } else { low_target };
+                let p = sigmoid(slope * score + intercept);
+                let w = p * (1.0 - p);
+                g_slope += (p - target) * score;
+                g_intercept += p - target;
+                h_ss += w * score * score;
+                h_si += w * score;
+                h_ii += w;
+            }
+            let determinant = h_ss * h_ii - h_si * h_si;
+            let step_slope = (h_ii * g_slope - h_si * g_intercept) / determinant;
+            let step_intercept = (h_ss * g_intercept - h_si * g_slope) / determinant;
+            slope -= step_slope;
+            intercept -= step_intercept;
+            if step_slope.abs() < 1e-9 && step_intercept.abs() < 1e-9 {
+                break;
+            }
+        }
+        Ok(Calibration::Platt { slope, intercept })
+    }
+
+    /// Fits a non-decreasing function to labeled scores, where true labels are anomalies, with the pool adjacent
+    /// violators algorithm.
+    pub fn isotonic(scores: &[f64], labels: &[bool]) -> Result<Calibration, ForestError> {
+        check_labels(scores, labels)?;
+        let mut pairs: Vec<(f64, f64)> = scores.iter().zip(labels).map(|(score, label)| (*score, if *label { 1.0 } else { 0.0 })).collect();
+        pairs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Each block holds the sum of its scores, the sum of its labels, and its size.
+        let mut blocks: Vec<(f64, f64, f64)> = Vec::new();
+        for (score, label) in pairs {
+            blocks.push((score, label, 1.0));
+            while blocks.len() > 1 {
+                let (last, previous) = (blocks[blocks.len() - 1], blocks[blocks.len() - 2]);
+                if previous.1 / previous.2 < last.1 / last.2 {
+                    break;
+                }
+                blocks.pop();
+                *blocks.last_mut().unwrap() = (previous.0 + last.0, previous.1 + last.1, previous.2 + last.2);
+            }
+        }
+        Ok(Calibration::Isotonic {
+            scores: blocks.iter().map(|block| block.0 / block.2).collect(),
+            probabilities: blocks.iter().map(|block| block.1 / block.2).collect(),
+        })
+    }
+
+    /// Returns the calibrated probability that a sample with the given normalized score is an outlier.
+    pub fn probability(&self, score: f64) -> f64 {
+        match self {
+            Calibration::Tail { threshold, scale, shape, .. } => {
+                if score <= *threshold {
+                    return 0.0;
+                }
+                let excess = (score - threshold) / scale;
+                let survival = if shape.abs() < 1e-9 {
+                    (-excess).exp()
+                } else {
+                    (1.0 + shape * excess).max(0.0).powf(-1.0 / shape)
+                };
+                1.0 - survival
+            }
+            Calibration::Platt { slope, intercept } => sigmoid(slope * score + intercept),
+            Calibration::Isotonic { scores, probabilities } => interpolate(scores, probabilities, score),
+        }
+    }
+}
+
+fn check_labels(scores: &[f64], labels: &[bool]) -> Result<(), ForestError> {
+    if scores.len() != labels.len() {
+        return Err(ForestError::InvalidParameter(format!("{} scores but {} labels", scores.len(), labels.len())));
+    }
+    if !labels.contains(&true) || !labels.contains(&false) {
+        return Err(ForestError::InvalidParameter("calibration needs both anomalous and normal labels".to_string()));
+    }
+    Ok(())
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Returns the quantile of sorted values, interpolating between neighbors.
+fn quantile(sorted: &[f64], level: f64) -> f64 {
+    let position = level * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+/// Linearly interpolates y at x between sorted points, holding the end values beyond them.
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+    let i = xs.partition_point(|value| *value <= x);
+    if i == 0 {
+        return ys[0];
+    }
+    if i == xs.len() {
+        return ys[xs.len() - 1];
+    }
+    let (x0, x1, y0, y1) = (xs[i - 1], xs[i], ys[i - 1], ys[i]);
+    if x1 > x0 { y0 + (y1 - y0) * (x - x0) / (x1 - x0) } else { y1 }
+}
@@ -0,0 +1,98 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Training and scoring `Forest`s from polars `DataFrame`s.
+
+extern crate polars;
+
+use self::polars::prelude::{DataFrame, DataType, Float64Chunked, NamedFrom, Series};
+use std::collections::HashMap;
+use crate::isolation_forest::{Forest, ForestError};
+
+/// Converts a column to f64 values. Numeric columns are cast, booleans are encoded as 0 and 1, and dates, times,
+/// and datetimes are encoded as their underlying integer values. Other types are rejected.
+fn column_to_f64(df: &DataFrame, column_name: &str) -> Result<Float64Chunked, ForestError> {
+    let column = df.column(column_name).map_err(|_| ForestError::MissingColumn(column_name.to_string()))?;
+    let series = column.as_materialized_series();
+    let data_type = series.dtype();
+    let unsupported = || ForestError::UnsupportedColumnType { column: column_name.to_string(), data_type: data_type.to_string() };
+
+    let series = if data_type.is_primitive_numeric() || data_type.is_bool() {
+        series.cast(&DataType::Float64).map_err(|_| unsupported())?
+    }
+    else if data_type.is_temporal() {
+        let series = series.to_physical_repr();
+        series.cast(&DataType::Float64).map_err(|_| unsupported())?
+    }
+    else {
+        return Err(unsupported());
+    };
+    series.f64().cloned().map_err(|_| unsupported())
+}
+
+impl Forest {
+    /// Trains the forest on a data frame, replacing any existing training data and trees. Each named column becomes
+    /// a feature of the same name; if no column names are given, every column is used. Nulls are treated as missing
+    /// values, so they are left out of training and handled by the missing value policy when scoring.
+    pub fn fit_dataframe(&mut self, df: &DataFrame, column_names: &[&str]) -> Result<(), ForestError> {
+        let column_names: Vec<String> = if column_names.is_empty() {
+            df.get_column_names().iter().map(|column_name| column_name.to_string()).collect()
+        }
+        else {
+            column_names.iter().map(|column_name| column_name.to_string()).collect()
+        };
+
+        let mut columns = HashMap::new();
+        for column_name in &column_names {
+            let values = column_to_f64(df, column_name)?;
+            columns.insert(column_name.clone(), values.into_iter().map(|value| value.unwrap_or(f64::NAN)).collect());
+        }
+
+        self.set_columns(columns, column_names);
+        self.create();
+        Ok(())
+    }
+
+    /// Scores each row of the data frame against the entire forest of trees, returning a series of normalized scores
+    /// named `normalized_score`. Values close to 1 indicate anomalies and values close to zero indicate normal values.
+    /// Features without a column in the data frame, and nulls, are handled by the missing value policy.
+    pub fn score_dataframe(&self, df: &DataFrame) -> Result<Series, ForestError> {
+        let mut columns = Vec::new();
+        for column_name in self.column_names() {
+            if df.column(&column_name).is_ok() {
+                let values: Vec<Option<f64>> = column_to_f64(df, &column_name)?.into_iter().collect();
+                columns.push((column_name, values));
+            }
+        }
+
+        let scores: Vec<f64> = (0..df.height()).map(|row| {
+            self.normalized_score_with(&|feature_name: &str| {
+                columns.iter()
+                    .find(|(column_name, _)| column_name == feature_name)
+                    .and_then(|(_, values)| values[row])
+                    .filter(|value| !value.is_nan())
+            })
+        }).collect();
+        Ok(Series::new("normalized_score".into(), scores))
+    }
+}
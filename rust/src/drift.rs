@@ -0,0 +1,260 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Detects when incoming data has drifted away from the data a forest was trained on, by comparing each feature's
+//! distribution, and the distribution of scores, with the population stability index and the two-sample
+//! Kolmogorov-Smirnov test.
+
+extern crate serde;
+
+use self::serde::Serialize;
+use crate::isolation_forest::{Float64Vec, Forest, ForestError, Sample};
+
+const MIN_FRACTION: f64 = 1e-4; // Keeps empty bins from making the population stability index infinite
+
+/// How far a distribution has moved away from its reference distribution.
+#[derive(Clone, Debug, Serialize)]
+pub struct DistributionShift {
+    pub psi: f64,          // Population stability index over bins of the reference distribution's quantiles
+    pub ks_statistic: f64, // Largest distance between the two empirical distribution functions
+    pub ks_p_value: f64,   // Probability of a distance at least this large if nothing drifted
+}
+
+/// The drift of a single feature.
+#[derive(Clone, Debug, Serialize)]
+pub struct FeatureDrift {
+    pub feature_name: String,
+    pub shift: Option<DistributionShift>, // None if no sample in the batch has the feature
+    pub missing_fraction: f64, // Fraction of the batch that lacks the feature
+    pub reference_missing_fraction: f64, // Fraction of the training data that lacks the feature
+    pub drifted: bool,
+}
+
+/// The drift of the forest's normalized scores.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScoreDrift {
+    pub shift: DistributionShift,
+    pub reference_mean: f64,
+    pub batch_mean: f64,
+    pub drifted: bool,
+}
+
+/// Describes how a batch of samples differs from the training data.
+#[derive(Clone, Debug, Serialize)]
+pub struct DriftReport {
+    pub num_samples: usize,
+    pub features: Vec<FeatureDrift>, // Sorted by feature name
+    pub scores: Option<ScoreDrift>,  // None if there were no reference scores
+}
+
+impl DriftReport {
+    /// Returns true if any feature, or the scores, drifted.
+    pub fn drifted(&self) -> bool {
+        self.features.iter().any(|feature| feature.drifted) || self.scores.as_ref().is_some_and(|scores| scores.drifted)
+    }
+
+    /// Returns the names of the features that drifted.
+    pub fn drifted_features(&self) -> Vec<&str> {
+        self.features.iter().filter(|feature| feature.drifted).map(|feature| feature.feature_name.as_str()).collect()
+    }
+}
+
+/// Compares batches of incoming samples with the training data of a forest.
+pub struct DriftMonitor<'a> {
+    forest: &'a Forest,
+    reference_scores: Float64Vec, // Sorted
+    num_reference_rows: usize, // Number of training rows, estimated by the most common feature
+    num_bins: usize,
+    psi_threshold: f64,
+    significance: f64,
+    missing_threshold: f64,
+}
+
+impl<'a> DriftMonitor<'a> {
+    /// Creates a monitor whose reference scores are the forest's scores on its training rows. A loaded forest has
+    /// no training rows, so its reference scores must be given with `reference_samples`.
+    pub fn new(forest: &'a Forest) -> DriftMonitor<'a> {
+        let mut reference_scores = forest.training_scores();
+        reference_scores.sort_unstable_by(f64::total_cmp);
+        let num_reference_rows = forest.feature_names().iter().map(|feature_name| forest.training_values(feature_name).map_or(0, |values| values.len())).max().unwrap_or(0);
+        DriftMonitor { forest, reference_scores, num_reference_rows, num_bins: 10, psi_threshold: 0.2, significance: 0.01, missing_threshold: 0.1 }
+    }
+
+    /// Takes the reference scores from samples that the forest was not trained on, such as a validation set.
+    pub fn reference_samples(mut self, samples: &[Sample]) -> Self {
+        self.reference_scores = samples.iter().map(|sample| self.forest.normalized_score(sample)).collect();
+        self.reference_scores.sort_unstable_by(f64::total_cmp);
+        self
+    }
+
+    /// Sets the number of quantile bins used by the population stability index, 10 by default.
+    pub fn num_bins(mut self, num_bins: usize) -> Self {
+        self.num_bins = num_bins;
+        self
+    }
+
+    /// Sets the population stability index above which a distribution has drifted, 0.2 by default.
+    pub fn psi_threshold(mut self, psi_threshold: f64) -> Self {
+        self.psi_threshold = psi_threshold;
+        self
+    }
+
+    /// Sets the Kolmogorov-Smirnov p-value below which a distribution has drifted, 0.01 by default.
+    pub fn significance(mut self, significance: f64) -> Self {
+        self.significance = significance;
+        self
+    }
+
+    /// Sets how much larger the fraction of the batch missing a feature may be than the fraction of the training data
+    /// missing it before the feature has drifted, 0.1 by default.
+    pub fn missing_threshold(mut self, missing_threshold: f64) -> Self {
+        self.missing_threshold = missing_threshold;
+        self
+    }
+
+    /// Compares a batch of samples with the training data, one feature at a time, and compares the batch's scores
+    /// with the reference scores.
+    pub fn check(&self, batch: &[Sample]) -> Result<DriftReport, ForestError> {
+        if batch.is_empty() {
+            return Err(ForestError::InvalidParameter("drift needs a non-empty batch".to_string()));
+        }
+        if self.num_bins < 2 {
+            return Err(ForestError::InvalidParameter("drift needs at least 2 bins".to_string()));
+        }
+
+        let mut features = Vec::new();
+        for feature_name in self.forest.feature_names() {
+            let reference = self.forest.training_values(&feature_name).unwrap_or_default();
+            let mut values: Float64Vec = batch.iter().filter_map(|sample| sample.feature_value(&feature_name)).collect();
+            if reference.is_empty() {
+                continue;
+            }
+
+            // A feature that the whole batch lacks has no distribution to compare, but has still drifted.
+            let missing_fraction = 1.0 - values.len() as f64 / batch.len() as f64;
+            let reference_missing_fraction = 1.0 - reference.len() as f64 / self.num_reference_rows as f64;
+            let shift = if values.is_empty() {
+                None
+            } else {
+                values.sort_unstable_by(f64::total_cmp);
+                Some(self.compare(reference, &values))
+            };
+            let drifted = missing_fraction - reference_missing_fraction > self.missing_threshold || shift.as_ref().is_none_or(|shift| self.drifted(shift));
+            features.push(FeatureDrift { feature_name, shift, missing_fraction, reference_missing_fraction, drifted });
+        }
+
+        let scores = if self.reference_scores.is_empty() {
+            None
+        } else {
+            let mut batch_scores: Float64Vec = batch.iter().map(|sample| self.forest.normalized_score(sample)).collect();
+            batch_scores.sort_unstable_by(f64::total_cmp);
+            let shift = self.compare(&self.reference_scores, &batch_scores);
+            Some(ScoreDrift {
+                drifted: self.drifted(&shift),
+                shift,
+                reference_mean: mean(&self.reference_scores),
+                batch_mean: mean(&batch_scores),
+            })
+        };
+
+        Ok(DriftReport { num_samples: batch.len(), features, scores })
+    }
+
+    fn drifted(&self, shift: &DistributionShift) -> bool {
+        shift.psi > self.psi_threshold || shift.ks_p_value < self.significance
+    }
+
+    /// Compares two sorted lists of values.
+    fn compare(&self, reference: &[f64], values: &[f64]) -> DistributionShift {
+        let ks_statistic = ks_statistic(reference, values);
+        DistributionShift {
+            psi: population_stability_index(reference, values, self.num_bins),
+            ks_statistic,
+            ks_p_value: ks_p_value(ks_statistic, reference.len(), values.len()),
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Bins both sorted lists by the quantiles of the reference and sums (b - r) ln(b / r) over the bins, where b and
+/// r are the fractions of each list in the bin.
+fn population_stability_index(reference: &[f64], values: &[f64], num_bins: usize) -> f64 {
+    let mut edges: Float64Vec = (1..num_bins).map(|i| reference[i * reference.len() / num_bins]).collect();
+    edges.dedup();
+
+    let fractions = |sorted: &[f64]| -> Float64Vec {
+        let mut below = 0;
+        let mut fractions: Float64Vec = edges.iter().map(|edge| {
+            let count = sorted.partition_point(|value| value < edge);
+            let fraction = (count - below) as f64 / sorted.len() as f64;
+            below = count;
+            fraction
+        }).collect();
+        fractions.push((sorted.len() - below) as f64 / sorted.len() as f64);
+        fractions
+    };
+    fractions(reference).iter().zip(fractions(values)).map(|(r, b)| {
+        let (r, b) = (r.max(MIN_FRACTION), b.max(MIN_FRACTION));
+        (b - r) * (b / r).ln()
+    }).sum()
+}
+
+/// The two-sample Kolmogorov-Smirnov statistic of two sorted lists.
+fn ks_statistic(a: &[f64], b: &[f64]) -> f64 {
+    let (mut i, mut j) = (0, 0);
+    let mut statistic: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        let value = a[i].min(b[j]);
+        while i < a.len() && a[i] <= value {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= value {
+            j += 1;
+        }
+        statistic = statistic.max((i as f64 / a.len() as f64 - j as f64 / b.len() as f64).abs());
+    }
+    statistic
+}
+
+/// The asymptotic p-value of a two-sample Kolmogorov-Smirnov statistic (Numerical Recipes, section 14.3).
+fn ks_p_value(statistic: f64, n: usize, m: usize) -> f64 {
+    let effective_n = (n as f64 * m as f64 / (n + m) as f64).sqrt();
+    let lambda = (effective_n + 0.12 + 0.11 / effective_n) * statistic;
+    if lambda < 0.2 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    let mut sign = 2.0;
+    for j in 1..=100 {
+        let term = sign * (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += term;
+        if term.abs() < 1e-10 {
+            break;
+        }
+        sign = -sign;
+    }
+    sum.clamp(0.0, 1.0)
+}
@@ -0,0 +1,304 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Half-Space Trees, a streaming anomaly detector (Tan, Ting & Liu, "Fast Anomaly Detection for Streaming Data", 2011).
+//!
+//! The trees have a fixed structure, built from the range of each feature before any data is seen. Each node counts
+//! how many samples from the most recent complete window of the stream passed through it (the reference mass) and how
+//! many from the current window have (the latest mass). When a window is complete, the latest masses become the
+//! reference masses. Samples that fall in regions with little reference mass are anomalous.
+
+extern crate rand;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::isolation_forest::{ForestError, Sample};
+
+const MAX_DEPTH: u32 = 20; // Deepest trees allowed; each tree stores 2^(depth + 1) - 1 nodes
+const MAX_NODE_BYTES: usize = 1 << 30; // Most memory the nodes of all of the trees may take
+
+/// Tree node, used internally. The tree is complete, so nodes are stored in a single vector with the children of
+/// node i at 2i + 1 and 2i + 2.
+#[derive(Clone)]
+struct Node {
+    feature_index: usize, // Index of the feature used to split, not used by leaves
+    split_value: f64,
+    reference_mass: u32, // Number of samples from the last complete window that passed through the node
+    latest_mass: u32, // Number of samples from the current window that passed through the node
+}
+
+type NodeList = Vec<Node>;
+
+/// Half-Space Trees implementation.
+pub struct HalfSpaceTrees {
+    feature_names: Vec<String>,
+    trees: Vec<NodeList>,
+    max_depth: u32, // Depth of the leaves; the root is at depth zero
+    window_size: usize, // Number of samples in each window of the stream
+    num_in_window: usize, // Number of samples seen so far in the current window
+    num_windows: usize, // Number of complete windows
+}
+
+/// Configures `HalfSpaceTrees`. The defaults follow the paper: 25 trees of depth 15 and windows of 250 samples.
+#[derive(Clone, Debug)]
+pub struct HalfSpaceTreesBuilder {
+    num_trees: u32,
+    max_depth: u32,
+    window_size: usize,
+    seed: Option<u64>,
+}
+
+impl Default for HalfSpaceTreesBuilder {
+    fn default() -> HalfSpaceTreesBuilder {
+        HalfSpaceTreesBuilder { num_trees: 25, max_depth: 15, window_size: 250, seed: None }
+    }
+}
+
+impl HalfSpaceTreesBuilder {
+    pub fn new() -> HalfSpaceTreesBuilder {
+        HalfSpaceTreesBuilder::default()
+    }
+
+    /// Sets the number of trees to build.
+    pub fn num_trees(mut self, num_trees: u32) -> HalfSpaceTreesBuilder {
+        self.num_trees = num_trees;
+        self
+    }
+
+    /// Sets the depth of the leaves, from 1 to 20. The nodes of all of the trees must also fit in 1 GiB.
+    pub fn max_depth(mut self, max_depth: u32) -> HalfSpaceTreesBuilder {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the number of samples in each window of the stream.
+    pub fn window_size(mut self, window_size: usize) -> HalfSpaceTreesBuilder {
+        self.window_size = window_size;
+        self
+    }
+
+    /// Seeds the random number generator, so that the same feature ranges always produce the same trees.
+    pub fn seed(mut self, seed: u64) -> HalfSpaceTreesBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    fn validate(&self, feature_ranges: &[(&str, f64, f64)]) -> Result<(), String> {
+        if self.num_trees == 0 {
+            return Err("num_trees must be at least 1".to_string());
+        }
+        if self.max_depth == 0 || self.max_depth > MAX_DEPTH {
+            return Err(format!("max_depth must be from 1 to {}, got {}", MAX_DEPTH, self.max_depth));
+        }
+        let node_bytes = self.num_trees as usize * ((1_usize << (self.max_depth + 1)) - 1) * std::mem::size_of::<Node>();
+        if node_bytes > MAX_NODE_BYTES {
+            return Err(format!("{} trees of depth {} would take {} bytes, more than the limit of {}", self.num_trees, self.max_depth, node_bytes, MAX_NODE_BYTES));
+        }
+        if self.window_size == 0 {
+            return Err("window_size must be at least 1".to_string());
+        }
+        if feature_ranges.is_empty() {
+            return Err("at least one feature range is needed".to_string());
+        }
+        for (name, min, max) in feature_ranges {
+            if !(min.is_finite() && max.is_finite() && min <= max) {
+                return Err(format!("the range of {} must be finite with min <= max, got {} to {}", name, min, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the trees from the range, given as (name, min, max), of each feature that will be seen in the stream.
+    pub fn build(self, feature_ranges: &[(&str, f64, f64)]) -> Result<HalfSpaceTrees, ForestError> {
+        self.validate(feature_ranges).map_err(ForestError::InvalidParameter)?;
+
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+        let feature_names = feature_ranges.iter().map(|(name, _, _)| name.to_string()).collect();
+        let mut trees = Vec::with_capacity(self.num_trees as usize);
+
+        for _i in 0..self.num_trees {
+            // Perturb the work space of each tree, per the paper, so that the trees differ.
+            let mut ranges: Vec<(f64, f64)> = feature_ranges.iter().map(|(_, min, max)| {
+                let split = rng.random_range(*min..=*max);
+                let half_range = 2.0 * (split - min).max(max - split);
+                (split - half_range, split + half_range)
+            }).collect();
+
+            let num_nodes = (1usize << (self.max_depth + 1)) - 1;
+            let mut tree = vec![Node { feature_index: 0, split_value: 0.0, reference_mass: 0, latest_mass: 0 }; num_nodes];
+            HalfSpaceTrees::create_tree(&mut tree, 0, 0, self.max_depth, &mut ranges, &mut rng);
+            trees.push(tree);
+        }
+
+        Ok(HalfSpaceTrees { feature_names, trees, max_depth: self.max_depth, window_size: self.window_size, num_in_window: 0, num_windows: 0 })
+    }
+
+    /// Creates the trees from the range of each feature in the given samples. Returns an error if there are no
+    /// feature values.
+    pub fn build_from_samples(self, samples: &[Sample]) -> Result<HalfSpaceTrees, ForestError> {
+        let mut feature_ranges: Vec<(String, f64, f64)> = Vec::new();
+
+        for sample in samples {
+            for (name, value) in sample.feature_values() {
+                match feature_ranges.iter_mut().find(|(range_name, _, _)| range_name == name) {
+                    Some(range) => {
+                        range.1 = range.1.min(value);
+                        range.2 = range.2.max(value);
+                    }
+                    None => {
+                        feature_ranges.push((name.to_string(), value, value));
+                    }
+                }
+            }
+        }
+
+        let feature_ranges: Vec<(&str, f64, f64)> = feature_ranges.iter().map(|(name, min, max)| (name.as_str(), *min, *max)).collect();
+        self.build(&feature_ranges)
+    }
+}
+
+impl HalfSpaceTrees {
+    /// Returns a builder for configuring the trees, including a seed.
+    pub fn builder() -> HalfSpaceTreesBuilder {
+        HalfSpaceTreesBuilder::new()
+    }
+
+    /// Creates the trees from the range, given as (name, min, max), of each feature that will be seen in the stream.
+    /// Returns an error if there are no trees, no features, a depth outside 1 to 30, an empty window, or a range
+    /// that isn't finite with min <= max.
+    pub fn new(num_trees: u32, max_depth: u32, window_size: usize, feature_ranges: &[(&str, f64, f64)]) -> Result<HalfSpaceTrees, ForestError> {
+        HalfSpaceTrees::builder().num_trees(num_trees).max_depth(max_depth).window_size(window_size).build(feature_ranges)
+    }
+
+    /// Creates the trees from the range of each feature in the given samples.
+    pub fn from_samples(num_trees: u32, max_depth: u32, window_size: usize, samples: &[Sample]) -> Result<HalfSpaceTrees, ForestError> {
+        HalfSpaceTrees::builder().num_trees(num_trees).max_depth(max_depth).window_size(window_size).build_from_samples(samples)
+    }
+
+    /// Splits the node at the midpoint of a randomly selected feature's range and recurses.
+    fn create_tree(tree: &mut NodeList, index: usize, depth: u32, max_depth: u32, ranges: &mut Vec<(f64, f64)>, rng: &mut StdRng) {
+        if depth >= max_depth || ranges.is_empty() {
+            return;
+        }
+
+        let feature_index = rng.random_range(0..ranges.len());
+        let (min, max) = ranges[feature_index];
+        let split_value = (min + max) / 2.0;
+        tree[index].feature_index = feature_index;
+        tree[index].split_value = split_value;
+
+        ranges[feature_index] = (min, split_value);
+        HalfSpaceTrees::create_tree(tree, 2 * index + 1, depth + 1, max_depth, ranges, rng);
+        ranges[feature_index] = (split_value, max);
+        HalfSpaceTrees::create_tree(tree, 2 * index + 2, depth + 1, max_depth, ranges, rng);
+        ranges[feature_index] = (min, max);
+    }
+
+    /// Returns true once the first window is complete. Until then there is no reference profile to score against.
+    pub fn is_ready(&self) -> bool {
+        self.num_windows > 0
+    }
+
+    /// Adds a sample from the stream to the latest mass profile. When the window is complete, the latest profile
+    /// replaces the reference profile.
+    pub fn update(&mut self, sample: &Sample) {
+        for tree_index in 0..self.trees.len() {
+            let mut index = 0;
+            let mut depth = 0;
+
+            loop {
+                self.trees[tree_index][index].latest_mass += 1;
+                if depth >= self.max_depth {
+                    break;
+                }
+
+                let node = &self.trees[tree_index][index];
+                match sample.feature_value(&self.feature_names[node.feature_index]) {
+                    Some(value) => {
+                        index = if value < node.split_value { 2 * index + 1 } else { 2 * index + 2 };
+                        depth += 1;
+                    }
+                    None => {
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.num_in_window += 1;
+        if self.num_in_window >= self.window_size {
+            for tree in self.trees.iter_mut() {
+                for node in tree.iter_mut() {
+                    node.reference_mass = node.latest_mass;
+                    node.latest_mass = 0;
+                }
+            }
+            self.num_in_window = 0;
+            self.num_windows += 1;
+        }
+    }
+
+    /// Scores the sample against a single tree, per the paper: the reference mass of the deepest node reached,
+    /// scaled by two to the power of its depth. Nodes with too little mass to be meaningful end the path early.
+    fn score_tree(&self, sample: &Sample, tree: &NodeList, index: usize, depth: u32) -> f64 {
+        let node = &tree[index];
+        let size_limit = 0.1 * self.window_size as f64;
+
+        if depth >= self.max_depth || (node.reference_mass as f64) < size_limit {
+            return node.reference_mass as f64 * 2.0_f64.powi(depth as i32);
+        }
+
+        match sample.feature_value(&self.feature_names[node.feature_index]) {
+            Some(value) => {
+                let child = if value < node.split_value { 2 * index + 1 } else { 2 * index + 2 };
+                self.score_tree(sample, tree, child, depth + 1)
+            }
+            None => {
+                // If the sample doesn't have the feature then take both sides of the tree and average the scores together.
+                let left_score = self.score_tree(sample, tree, 2 * index + 1, depth + 1);
+                let right_score = self.score_tree(sample, tree, 2 * index + 2, depth + 1);
+                (left_score + right_score) / 2.0
+            }
+        }
+    }
+
+    /// Scores the sample against the reference profile. Result is the average mass score across the trees, where
+    /// higher values indicate normal samples.
+    pub fn score(&self, sample: &Sample) -> f64 {
+        if self.trees.is_empty() {
+            return 0.0;
+        }
+        self.trees.iter().map(|tree| self.score_tree(sample, tree, 0, 0)).sum::<f64>() / self.trees.len() as f64
+    }
+
+    /// Scores the sample against the reference profile. Result is normalized so that values close to 1 indicate
+    /// anomalies and values close to zero indicate normal values, with 0.5 corresponding to a sample in a region as
+    /// dense as if the window were spread uniformly over the feature ranges. Every sample scores 1.0 until the first
+    /// window is complete.
+    pub fn normalized_score(&self, sample: &Sample) -> f64 {
+        2.0_f64.powf(-self.score(sample) / self.window_size as f64)
+    }
+}
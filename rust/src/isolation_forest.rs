@@ -25,40 +25,113 @@ extern crate rand;
 extern crate serde;
 extern crate serde_json;
 
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use std::collections::HashMap;
-use self::serde::{
-	ser::{SerializeStruct, Serializer}, Serialize, Deserialize
-};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use self::serde::{Serialize, Deserialize};
+use crate::builder::ForestBuilder;
+use crate::calibration::Calibration;
+use crate::splitter::{NodeData, Split, Splitter, ValueUniform};
+use crate::training_data::TrainingData;
 
 /// Each feature has a name and value.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Feature {
     pub name: String,
-    value: u64,
+    value: f64,
 }
 
 impl Feature {
-    pub fn new (name: &str, value: u64) -> Feature {
-        Feature { name: name.to_string(), value }
+    /// Creates a feature from an f64 or, as in earlier versions, an integer value such as a u64.
+    pub fn new<V: FeatureValue> (name: &str, value: V) -> Feature {
+        Feature { name: name.to_string(), value: value.to_f64() }
     }
 }
 
+/// A value that can be given to `Feature::new`. Feature values are stored as f64, so integers above 2^53 lose
+/// precision.
+pub trait FeatureValue {
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_feature_value {
+    ($($t:ty),*) => {
+        $(impl FeatureValue for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        })*
+    };
+}
+
+impl_feature_value!(f64, f32, u64, u32, usize, i64, i32);
+
 pub type FeatureList = Vec<Feature>;
+pub type SampleList = Vec<Sample>;
 pub type Uint64Vec = Vec<u64>;
-pub type FeatureNameToValuesMap = HashMap<String, Uint64Vec>;
+pub type Float64Vec = Vec<f64>;
+pub type FeatureNameToValuesMap = HashMap<String, Float64Vec>;
+
+const STREAM_SAMPLES_PER_TREE: usize = 256; // Reservoir space per tree when streaming with a fractional max samples
+const MAX_WEIGHTING_ROWS: usize = 256; // Most unlabeled rows used as the normal rows when weighting trees by labels
+const FEEDBACK_LEARNING_RATE: f64 = 0.5; // How far a single piece of feedback moves the tree weights
 
 /// This class represents a sample.
-/// Each sample has a name and list of features.
+/// Each sample has a name and list of features, and optionally a training weight and a label.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Sample {
+    #[serde(default)]
     pub name: String,
     features: FeatureList,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<f64>, // Relative likelihood of the sample being drawn when building a tree, only used during training
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<bool>, // True if the sample is a known anomaly, false if it is known to be normal, only used during training
 }
 
 impl Sample {
     pub fn new (sample_name: &str) -> Sample {
-        Sample { name: sample_name.to_string(), features: Sample::create_feature_list() }
+        Sample { name: sample_name.to_string(), features: Sample::create_feature_list(), weight: None, label: None }
+    }
+
+    /// Sets the sample's training weight. Samples with lower weights are less likely to be used when building each
+    /// tree, which is useful for down-weighting noisy sources or replayed data. Weights do not affect scoring.
+    pub fn set_weight(&mut self, weight: f64) {
+        assert!(weight.is_finite() && weight >= 0.0, "Sample weights must be finite and non-negative");
+        self.weight = Some(weight);
+    }
+
+    /// Labels the sample as a known anomaly, or as known to be normal. When a forest's training samples include
+    /// known anomalies, the trees that isolate them sooner than normal samples are given more weight.
+    pub fn set_label(&mut self, is_anomaly: bool) {
+        self.label = Some(is_anomaly);
+    }
+
+    /// Returns the sample's label, true for a known anomaly, or None if it is unlabeled.
+    pub fn label(&self) -> Option<bool> {
+        self.label
+    }
+
+    /// Returns the value of the named feature, or None if the sample does not have the feature.
+    pub fn feature_value(&self, feature_name: &str) -> Option<f64> {
+        self.features.iter().find(|feature| feature.name == feature_name).map(|feature| feature.value)
+    }
+
+    /// Returns the name and value of each of the sample's features.
+    pub fn feature_values(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.features.iter().map(|feature| (feature.name.as_str(), feature.value))
+    }
+
+    /// Returns the sample's training weight, which defaults to 1.0.
+    pub fn weight(&self) -> f64 {
+        self.weight.unwrap_or(1.0)
+    }
+
+    /// Returns true if the sample was given a training weight.
+    pub(crate) fn has_weight(&self) -> bool {
+        self.weight.is_some()
     }
 
     fn create_feature_list() -> FeatureList {
@@ -72,84 +145,594 @@ impl Sample {
 }
 
 /// Tree node, used internally.
+/// Nodes split either on a single feature or, if the hyperplane is not empty, on a weighted sum of features.
 #[derive(Serialize, Deserialize)]
 struct Node {
     feature_name: String,
-    split_value: u64,
+    split_value: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hyperplane: Vec<(String, f64)>, // Feature names and their coefficients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    range: Option<(f64, f64)>, // If set, samples whose value falls outside this range are isolated at this node
     left: NodeLink,
     right: NodeLink,
+    #[serde(default, skip_serializing_if = "Node::is_small_leaf")]
+    left_size: usize, // Number of training rows left unsplit in the left leaf, if the left child is a leaf
+    #[serde(default, skip_serializing_if = "Node::is_small_leaf")]
+    right_size: usize, // Number of training rows left unsplit in the right leaf, if the right child is a leaf
 }
 
 impl Node {
-    pub fn new (feature_name: &str, split_value: u64) -> Node {
-        Node { feature_name: feature_name.to_string(), split_value, left: None, right: None }
+    pub fn new (feature_name: &str, split_value: f64) -> Node {
+        Node { feature_name: feature_name.to_string(), split_value, hyperplane: Vec::new(), range: None, left: None, right: None, left_size: 0, right_size: 0 }
+    }
+
+    pub fn new_hyperplane (hyperplane: Vec<(String, f64)>, split_value: f64) -> Node {
+        Node { feature_name: String::new(), split_value, hyperplane, range: None, left: None, right: None, left_size: 0, right_size: 0 }
+    }
+
+    /// Leaves with fewer than two rows need no path length adjustment, so their sizes aren't serialized.
+    fn is_small_leaf(size: &usize) -> bool {
+        *size <= 1
     }
 }
 
 type NodeBox = Box<Node>;
 type NodeLink = Option<Box<Node>>;
-type NodeList = Vec<Box<Node>>;
+
+/// Describes where a tree came from, so trees can be traced after forests are grown, pruned, or merged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TreeProvenance {
+    pub origin: String, // Origin of the forest that built the tree
+    pub created: u64, // When the tree was built, in seconds since the Unix epoch
+    pub num_samples: usize, // Number of training samples the tree was built from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feature_names: Vec<String>, // Features the tree was allowed to split on, or empty if it could use all of them
+}
+
+/// A tree, along with its provenance and its weight in the forest's score.
+#[derive(Serialize, Deserialize)]
+struct Tree {
+    root: NodeBox,
+    provenance: TreeProvenance,
+    #[serde(default = "Tree::default_weight", skip_serializing_if = "Tree::has_default_weight")]
+    weight: f64,
+}
+
+impl Tree {
+    fn default_weight() -> f64 {
+        1.0
+    }
+
+    fn has_default_weight(weight: &f64) -> bool {
+        *weight == Tree::default_weight()
+    }
+}
+
+type TreeList = Vec<Tree>;
+
+/// Summary of a per-tree measurement across the forest.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Distribution {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl Distribution {
+    fn from_values(values: &[usize]) -> Distribution {
+        if values.is_empty() {
+            return Distribution::default();
+        }
+
+        let min = *values.iter().min().unwrap();
+        let max = *values.iter().max().unwrap();
+        let mean = values.iter().sum::<usize>() as f64 / values.len() as f64;
+        Distribution { min, max, mean }
+    }
+}
+
+/// Conditions that are likely to make the forest's scores meaningless.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ForestWarning {
+    NoTrainingData, // No samples were added before the forest was created
+    NoTreesBuilt, // Trees were requested, but none could be built
+    TreesDropped { requested: u32, built: usize }, // Some of the requested trees could not be built
+    ConstantFeature(String), // Every training sample has the same value for this feature
+    UnusedFeature(String), // The feature was never selected for a split
+}
+
+/// Statistics describing the trees in a forest, as returned by `Forest::stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ForestStats {
+    pub num_trees_requested: u32,
+    pub num_trees_built: usize,
+    pub tree_depths: Vec<usize>, // Depth of each tree, in the order the trees were built
+    pub tree_node_counts: Vec<usize>, // Number of nodes in each tree, in the order the trees were built
+    pub depth: Distribution,
+    pub node_count: Distribution,
+    pub feature_usage: HashMap<String, usize>, // Number of splits that use each feature
+    pub tree_feature_names: Vec<Vec<String>>, // Features each tree was allowed to split on, or empty if it could use all of them
+    pub warnings: Vec<ForestWarning>,
+}
+
+/// How to score a sample that does not have a value for a feature that a tree splits on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MissingValuePolicy {
+    #[default]
+    AverageBranches, // Follow both branches and average their path lengths
+    ImputeMedian, // Use the median of the feature's training values
+}
+
+/// How many of the training samples each tree is built from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaxSamples {
+    Count(usize), // At most this many samples
+    Fraction(f64), // This fraction of the samples, rounded up
+}
+
+impl Default for MaxSamples {
+    fn default() -> MaxSamples {
+        MaxSamples::Fraction(1.0)
+    }
+}
+
+impl MaxSamples {
+    /// Returns the number of samples to build each tree from, out of the given number of training samples.
+    pub fn resolve(&self, num_samples: usize) -> usize {
+        match *self {
+            MaxSamples::Count(count) => count.min(num_samples),
+            MaxSamples::Fraction(fraction) => ((num_samples as f64 * fraction).ceil() as usize).min(num_samples),
+        }
+    }
+}
+
+/// How the path lengths of the individual trees are combined into a sample's score.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Aggregator {
+    #[default]
+    Mean, // The average path length, as in the original paper
+    Median, // The median path length, which is less affected by a few unusual trees
+    TrimmedMean(f64), // The average after dropping this fraction of the shortest and of the longest path lengths
+    Min, // The shortest path length
+}
+
+impl Aggregator {
+    /// Combines the path lengths of the individual trees, each counting in proportion to its weight. Trees with
+    /// zero weight are ignored. Returns zero if there are no trees with weight.
+    pub fn aggregate_weighted(&self, depths: &[f64], weights: &[f64]) -> f64 {
+        if weights.windows(2).all(|pair| pair[0] == pair[1]) {
+            return self.aggregate(depths);
+        }
+
+        let mut pairs: Vec<(f64, f64)> = depths.iter().copied().zip(weights.iter().copied()).filter(|(_, weight)| *weight > 0.0).collect();
+        if pairs.is_empty() {
+            return 0.0;
+        }
+        pairs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let total_weight: f64 = pairs.iter().map(|(_, weight)| weight).sum();
+
+        // The weighted mean of the depths whose cumulative weight falls between the bounds.
+        let mean_between = |low: f64, high: f64| {
+            let (mut sum, mut sum_of_weights, mut cumulative_weight) = (0.0, 0.0, 0.0);
+            for (depth, weight) in &pairs {
+                let start = cumulative_weight;
+                cumulative_weight += weight;
+                let overlap = cumulative_weight.min(high) - start.max(low);
+                if overlap > 0.0 {
+                    sum += depth * overlap;
+                    sum_of_weights += overlap;
+                }
+            }
+            sum / sum_of_weights
+        };
+        match *self {
+            Aggregator::Mean => mean_between(0.0, total_weight),
+            Aggregator::Median => {
+                let mut cumulative_weight = 0.0;
+                pairs.iter().find(|(_, weight)| {
+                    cumulative_weight += weight;
+                    cumulative_weight >= total_weight / 2.0
+                }).map_or(pairs[pairs.len() - 1].0, |(depth, _)| *depth)
+            }
+            Aggregator::TrimmedMean(fraction) => mean_between(fraction * total_weight, (1.0 - fraction) * total_weight),
+            Aggregator::Min => pairs[0].0,
+        }
+    }
+
+    /// Combines the path lengths of the individual trees. Returns zero if there are none.
+    pub fn aggregate(&self, depths: &[f64]) -> f64 {
+        if depths.is_empty() {
+            return 0.0;
+        }
+
+        let mut sorted = depths.to_vec();
+        sorted.sort_unstable_by(f64::total_cmp);
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        match *self {
+            Aggregator::Mean => mean(depths),
+            Aggregator::Median => {
+                let middle = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) { (sorted[middle - 1] + sorted[middle]) / 2.0 } else { sorted[middle] }
+            }
+            Aggregator::TrimmedMean(fraction) => {
+                let num_trimmed = ((sorted.len() as f64 * fraction) as usize).min((sorted.len() - 1) / 2);
+                mean(&sorted[num_trimmed..sorted.len() - num_trimmed])
+            }
+            Aggregator::Min => sorted[0],
+        }
+    }
+}
+
+/// A normalized score, along with how much it varies across the trees, as returned by `Forest::score_with_uncertainty`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ScoreUncertainty {
+    pub mean: f64, // The normalized score
+    pub std: f64, // Standard deviation of the normalized scores of the individual trees, weighted by the tree weights
+    pub lower: f64, // Lower bound of the confidence interval for the normalized score
+    pub upper: f64, // Upper bound of the confidence interval for the normalized score
+}
+
+/// Returns the average path length of an unsuccessful search in a binary search tree of n items, per the original
+/// paper. This is the expected depth of a point in an isolation tree built from n samples.
+fn c(n: usize) -> f64 {
+    match n {
+        0 | 1 => 0.0,
+        2 => 1.0,
+        _ => {
+            let n = n as f64;
+            2.0 * ((n - 1.0).ln() + 0.5772156649) - 2.0 * (n - 1.0) / n
+        }
+    }
+}
+
+/// Returns the quantile of the standard normal distribution for the given probability, using Acklam's rational
+/// approximation, which is accurate to about 1e-9.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02, 1.38357751867269e+02, -3.066479806614716e+01, 2.506628277459239e+00];
+    const B: [f64; 5] = [-5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02, 6.680131188771972e+01, -1.328068155288572e+01];
+    const C: [f64; 6] = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00, -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00];
+    const D: [f64; 4] = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00, 3.754408661907416e+00];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    }
+    else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    }
+    else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Errors returned by forest operations.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ForestError {
+    SchemaMismatch { expected: Vec<String>, found: Vec<String> }, // The forests were trained on different features
+    ColumnCountMismatch { expected: usize, found: usize }, // The number of column names does not match the data
+    MissingColumn(String), // The named column does not exist in the data
+    UnsupportedColumnType { column: String, data_type: String }, // The column's values cannot be used as feature values
+    ReadError(String), // The data could not be read
+    WriteError(String), // The output could not be written
+    ParseError(String), // The serialized forest could not be parsed
+    InvalidParameter(String), // A forest option is out of range or conflicts with another option
+}
+
+impl fmt::Display for ForestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForestError::SchemaMismatch { expected, found } => {
+                write!(f, "Schema mismatch: expected features {:?}, found {:?}", expected, found)
+            }
+            ForestError::ColumnCountMismatch { expected, found } => {
+                write!(f, "Column count mismatch: expected {} column names, found {}", expected, found)
+            }
+            ForestError::MissingColumn(column) => {
+                write!(f, "Missing column: {}", column)
+            }
+            ForestError::UnsupportedColumnType { column, data_type } => {
+                write!(f, "Column {} has unsupported type {}", column, data_type)
+            }
+            ForestError::ReadError(message) => {
+                write!(f, "Read error: {}", message)
+            }
+            ForestError::WriteError(message) => {
+                write!(f, "Write error: {}", message)
+            }
+            ForestError::ParseError(message) => {
+                write!(f, "Parse error: {}", message)
+            }
+            ForestError::InvalidParameter(message) => {
+                write!(f, "Invalid parameter: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ForestError {}
 
 /// Isolation Forest implementation.
+/// The training samples are not serialized, only the feature values and the trees built from them.
+#[derive(Serialize, Deserialize)]
 pub struct Forest {
-    feature_values: FeatureNameToValuesMap, // Lists each feature and maps it to all unique values in the training set
-    trees: NodeList, // The decision trees that comprise the forest
+    #[serde(rename = "Origin", default)]
+    origin: String, // Identifies this forest in the provenance of the trees it builds
+    #[serde(rename = "Num Trees", default)]
     num_trees_to_create: u32, // The maximum number of trees to create
+    #[serde(rename = "Sub Sampling Size")]
     sub_sampling_size: u32, // The maximum depth of a tree
-}
-
-impl Serialize for Forest {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut s = serializer.serialize_struct("Forest", 3)?;
-        s.serialize_field("Sub Sampling Size", &self.sub_sampling_size)?;
-        s.serialize_field("Feature Values", &self.feature_values)?;
-        s.serialize_field("Trees", &self.trees)?;
-        s.end()
-    }
+    #[serde(rename = "Feature Values")]
+    feature_values: FeatureNameToValuesMap, // Lists each feature and maps it to all unique values in the training set
+    #[serde(skip)]
+    training_data: TrainingData, // The training samples, kept so that trees can be added later
+    #[serde(skip)]
+    num_trained_samples: usize, // The number of training samples that have already been used to build trees
+    #[serde(skip)]
+    num_valued_samples: usize, // The number of training samples whose values have been added to the feature values
+    #[serde(rename = "Column Names", default)]
+    column_names: Vec<String>, // Feature names, in column order, when trained from a dense matrix
+    #[serde(rename = "Missing Value Policy", default)]
+    missing_value_policy: MissingValuePolicy, // How to score samples that are missing a feature
+    #[serde(rename = "Aggregator", default)]
+    aggregator: Aggregator, // How the path lengths of the trees are combined into a score
+    #[serde(rename = "Max Samples", default)]
+    max_samples: MaxSamples, // How many of the training samples each tree is built from
+    #[serde(rename = "Max Features", default)]
+    max_features: usize, // How many randomly selected features each tree may split on, or zero for all of them
+    #[serde(rename = "Bootstrap", default)]
+    bootstrap: bool, // True if each tree's samples are drawn with replacement
+    #[serde(rename = "Seed", default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>, // Seeds the random number generator, so that the trees can be reproduced
+    #[serde(rename = "Contamination", default, skip_serializing_if = "Option::is_none")]
+    contamination: Option<f64>, // Expected fraction of anomalies in the training data
+    #[serde(rename = "Threshold", default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<f64>, // Normalized score above which samples are anomalies, found from the contamination
+    #[serde(rename = "Calibration", default, skip_serializing_if = "Option::is_none")]
+    calibration: Option<Calibration>, // Maps normalized scores to outlier probabilities
+    #[serde(skip, default = "Forest::unseeded_rng")]
+    rng: StdRng, // Source of the random choices made while building trees
+    #[serde(skip, default = "Forest::default_splitter")]
+    splitter: Box<dyn Splitter>, // Chooses the split at each node of the trees being built
+    #[serde(rename = "Trees")]
+    trees: TreeList, // The decision trees that comprise the forest, oldest first
 }
 
 impl Forest {
     pub fn new (num_trees_to_create: u32, sub_sampling_size: u32) -> Forest {
-        Forest { num_trees_to_create, sub_sampling_size, trees: Forest::initialize_trees(), feature_values: Forest::create_feature_name_to_values_map() }
+        Forest {
+            num_trees_to_create,
+            sub_sampling_size,
+            trees: Forest::initialize_trees(),
+            feature_values: Forest::create_feature_name_to_values_map(),
+            training_data: TrainingData::new(),
+            num_trained_samples: 0,
+            num_valued_samples: 0,
+            origin: String::new(),
+            column_names: Vec::new(),
+            missing_value_policy: MissingValuePolicy::default(),
+            aggregator: Aggregator::default(),
+            max_samples: MaxSamples::default(),
+            max_features: 0,
+            bootstrap: false,
+            seed: None,
+            contamination: None,
+            threshold: None,
+            calibration: None,
+            rng: Forest::unseeded_rng(),
+            splitter: Forest::default_splitter(),
+        }
+    }
+
+    /// Returns a builder for configuring a forest with named, validated options.
+    pub fn builder() -> ForestBuilder {
+        ForestBuilder::new()
+    }
+
+    fn unseeded_rng() -> StdRng {
+        StdRng::from_rng(&mut rand::rng())
+    }
+
+    /// Sets how many samples each tree is built from, and whether they are drawn with replacement.
+    pub(crate) fn set_sampling(&mut self, max_samples: MaxSamples, bootstrap: bool) {
+        self.max_samples = max_samples;
+        self.bootstrap = bootstrap;
+    }
+
+    /// Sets how many randomly selected features each tree may split on, or zero to let trees use all of them.
+    pub(crate) fn set_max_features(&mut self, max_features: usize) {
+        self.max_features = max_features;
+    }
+
+    /// Seeds the random number generator used to build trees.
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Sets the expected fraction of anomalies in the training data, from which the threshold is found.
+    pub(crate) fn set_contamination(&mut self, contamination: f64) {
+        self.contamination = Some(contamination);
+    }
+
+    /// Returns the seed given when the forest was built, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Returns the normalized score above which samples are considered anomalies. This is set when trees are built
+    /// if the forest was given a contamination, so that that fraction of the training samples are above it.
+    pub fn threshold(&self) -> Option<f64> {
+        self.threshold
+    }
+
+    /// Sets the calibration that `probability` applies to normalized scores. It is saved with the forest, but is not
+    /// refit when trees are added, pruned or merged.
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = Some(calibration);
+    }
+
+    /// Returns the calibration, if the forest has been calibrated.
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
     }
 
-    fn initialize_trees() -> NodeList {
-        let v: NodeList = vec![];
+    /// Calibrates the forest without labels by fitting the tail model to the scores of its training samples.
+    pub fn calibrate_tail(&mut self, tail_fraction: f64) -> Result<(), ForestError> {
+        if self.trees.is_empty() {
+            return Err(ForestError::InvalidParameter("the forest has no trees to calibrate".to_string()));
+        }
+        self.calibration = Some(Calibration::tail(&self.training_scores(), tail_fraction)?);
+        Ok(())
+    }
+
+    fn initialize_trees() -> TreeList {
+        let v: TreeList = vec![];
         v
     }
 
+    /// Sets the name recorded in the provenance of trees built by this forest, such as the cluster it was trained on.
+    pub fn set_origin(&mut self, origin: &str) {
+        self.origin = origin.to_string();
+    }
+
+    /// Sets how samples that are missing a feature are scored.
+    pub fn set_missing_value_policy(&mut self, missing_value_policy: MissingValuePolicy) {
+        self.missing_value_policy = missing_value_policy;
+    }
+
+    /// Returns how samples that are missing a feature are scored.
+    pub fn missing_value_policy(&self) -> MissingValuePolicy {
+        self.missing_value_policy
+    }
+
+    /// Sets how the path lengths of the trees are combined into a score.
+    pub fn set_aggregator(&mut self, aggregator: Aggregator) {
+        if let Aggregator::TrimmedMean(fraction) = aggregator {
+            assert!((0.0..0.5).contains(&fraction), "The trimmed fraction must be at least 0 and less than 0.5");
+        }
+        self.aggregator = aggregator;
+    }
+
+    /// Returns how the path lengths of the trees are combined into a score.
+    pub fn aggregator(&self) -> Aggregator {
+        self.aggregator
+    }
+
+    fn default_splitter() -> Box<dyn Splitter> {
+        Box::new(ValueUniform)
+    }
+
+    /// Sets how the split at each node is chosen for trees built from now on. The splitter is not serialized,
+    /// so a loaded forest uses the default `ValueUniform` splitter.
+    pub fn set_splitter<S: Splitter + 'static>(&mut self, splitter: S) {
+        self.splitter = Box::new(splitter);
+    }
+
+    /// Returns the number of trees in the forest.
+    pub fn num_trees(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Returns the provenance of each tree, oldest first.
+    pub fn provenance(&self) -> Vec<&TreeProvenance> {
+        self.trees.iter().map(|tree| &tree.provenance).collect()
+    }
+
     fn create_feature_name_to_values_map() -> FeatureNameToValuesMap {
-        let m = FeatureNameToValuesMap::new();
-        m
+        FeatureNameToValuesMap::new()
     }
 
     pub fn add_sample(&mut self, sample: Sample) {
-		// Add each of this sample's features to the list of known features
-		// with the corresponding set of unique values.
-
+		// Add each of this sample's features to the list of known features. Their values are added
+		// when the trees are built, so that they are only sorted once.
         for feature in &sample.features {
-            if self.feature_values.contains_key(&feature.name) {
-                let mut feature_value_set = self.feature_values[&feature.name].clone();
-                feature_value_set.push(feature.value);
-                feature_value_set.sort_unstable();
-                self.feature_values.insert(feature.name.clone(), feature_value_set);
+            self.feature_values.entry(feature.name.clone()).or_default();
+        }
+        self.training_data.push_sample(&sample);
+    }
+
+    /// Adds the values of the training samples added since the last update to the feature values, and sorts them.
+    fn update_feature_values(&mut self) {
+        let new_rows: Vec<usize> = (self.num_valued_samples..self.training_data.num_rows()).collect();
+        if new_rows.is_empty() {
+            return;
+        }
+        for (feature_name, values) in self.training_data.feature_values(&new_rows) {
+            let feature_value_set = self.feature_values.entry(feature_name).or_default();
+            feature_value_set.extend(values);
+            feature_value_set.sort_unstable_by(f64::total_cmp);
+        }
+        self.num_valued_samples = self.training_data.num_rows();
+    }
+
+    /// Trains the forest in one pass over the samples, replacing any training data and trees, and returns the
+    /// number of samples read. Only a uniform random reservoir of the samples is kept: the number of trees times
+    /// the max samples count, or times 256 if max samples is a fraction, which is then taken of the reservoir.
+    pub fn fit_from_iter<I: IntoIterator<Item = Sample>>(&mut self, samples: I) -> usize {
+        let samples_per_tree = match self.max_samples {
+            MaxSamples::Count(count) => count,
+            MaxSamples::Fraction(_) => STREAM_SAMPLES_PER_TREE,
+        };
+        let capacity = (self.num_trees_to_create as usize).max(1) * samples_per_tree;
+
+        let mut reservoir: SampleList = Vec::new();
+        let mut num_samples = 0;
+        for sample in samples {
+            num_samples += 1;
+            if reservoir.len() < capacity {
+                reservoir.push(sample);
             }
             else {
-                let mut feature_value_set = Vec::new();
-                feature_value_set.push(feature.value);
-                self.feature_values.insert(feature.name.clone(), feature_value_set);
+                let index = self.rng.random_range(0..num_samples);
+                if index < capacity {
+                    reservoir[index] = sample;
+                }
             }
         }
+
+        self.training_data = TrainingData::new();
+        for sample in &reservoir {
+            self.training_data.push_sample(sample);
+        }
+        let all_rows: Vec<usize> = (0..self.training_data.num_rows()).collect();
+        self.feature_values = self.training_data.feature_values(&all_rows);
+        self.num_valued_samples = all_rows.len();
+        self.create();
+        num_samples
     }
 
-    /// Creates and returns a single tree. As this is a recursive function, depth indicates the current depth of the recursion.
-    fn create_tree(&mut self, feature_values: FeatureNameToValuesMap, depth: u32) -> NodeLink {
+    /// Replaces the training data with columns that were collected without going through `Sample`s. Each column
+    /// has one value per row, with NaN for missing values. The column order is remembered for scoring dense matrices.
+    pub(crate) fn set_columns(&mut self, columns: HashMap<String, Float64Vec>, column_names: Vec<String>) {
+        self.training_data = TrainingData::from_columns(columns);
+        let all_rows: Vec<usize> = (0..self.training_data.num_rows()).collect();
+        self.feature_values = self.training_data.feature_values(&all_rows);
+        self.num_valued_samples = all_rows.len();
+        for column_name in &column_names {
+            self.feature_values.entry(column_name.clone()).or_default();
+        }
+        self.column_names = column_names;
+        self.num_trained_samples = 0;
+    }
+
+    /// Returns the feature names in the column order used for dense matrices. This is the order given
+    /// when the forest was trained from a matrix, otherwise the sorted feature names.
+    pub(crate) fn column_names(&self) -> Vec<String> {
+        if self.column_names.is_empty() { self.feature_names() } else { self.column_names.clone() }
+    }
+
+    /// Creates and returns a single tree from the given training rows. As this is a recursive function, depth
+    /// indicates the current depth of the recursion. Tree ranges holds the range of each feature over the tree's
+    /// rows, if the splitter checks them.
+    fn create_tree(&self, rows: &[usize], feature_names: &[String], tree_ranges: &HashMap<String, (f64, f64)>, depth: u32, rng: &mut StdRng) -> NodeLink {
 		// Sanity check.
-        let feature_values_len = feature_values.len();
-		if feature_values_len <= 1 {
+		if rows.len() <= 1 || feature_names.is_empty() {
 			return None;
 		}
 
@@ -158,173 +741,525 @@ impl Forest {
 			return None;
 		}
 
-		// Randomly select a feature.
-        let selected_feature_index = rand::rng().random_range(0..feature_values_len);
-        let selected_feature_name = feature_values.keys().nth(selected_feature_index);
-        let unwrapped_feature_name = selected_feature_name.unwrap();
+        // Let the splitter choose how to split the rows.
+        let node_data = NodeData::new(&self.training_data, rows, feature_names, depth);
+        let split = self.splitter.split(&node_data, rng)?;
+        let (left_rows, right_rows, range) = node_data.partition(&split);
+
+        // Create a tree node to hold the split.
+        let mut tree_root = match split {
+            Split::Feature { feature_name, split_value } => {
+                let mut node = Node::new(&feature_name, split_value);
+                node.range = tree_ranges.get(&feature_name).copied();
+                node
+            }
+            Split::Hyperplane { terms, split_value } => Node::new_hyperplane(terms, split_value),
+        };
+        if self.splitter.range_check() {
+            tree_root.range = Some(range);
+        }
+
+        // Create the subtrees.
+        tree_root.left = self.create_tree(&left_rows, feature_names, tree_ranges, depth + 1, rng);
+        tree_root.right = self.create_tree(&right_rows, feature_names, tree_ranges, depth + 1, rng);
+        if tree_root.left.is_none() {
+            tree_root.left_size = left_rows.len();
+        }
+        if tree_root.right.is_none() {
+            tree_root.right_size = right_rows.len();
+        }
+
+        Some(Box::new(tree_root))
+    }
+
+    /// Builds up to the specified number of trees from the training samples, starting with the given
+    /// sample, and appends them to the forest.
+    fn build_trees(&mut self, first_sample: usize, num_trees: u32) {
+        self.update_feature_values();
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let rows: Vec<usize> = (first_sample..self.training_data.num_rows()).collect();
+        let num_tree_rows = self.max_samples.resolve(rows.len());
+        let feature_names = self.training_data.feature_names();
+        let mut rng = self.rng.clone();
+        let mut trees = TreeList::new();
+
+    	for _i in 0..num_trees {
+            let tree_rows = self.training_data.draw_rows(&rows, num_tree_rows, self.bootstrap, &mut rng);
+            let tree_feature_names = self.draw_feature_names(&feature_names, &mut rng);
+            let tree_ranges = if self.splitter.tree_range_check() { self.tree_ranges(&tree_rows, &feature_names) } else { HashMap::new() };
+            let root = self.create_tree(&tree_rows, tree_feature_names.as_deref().unwrap_or(&feature_names), &tree_ranges, 0, &mut rng);
+            if let Some(root) = root {
+                let feature_names = tree_feature_names.unwrap_or_default();
+                let provenance = TreeProvenance { origin: self.origin.clone(), created, num_samples: tree_rows.len(), feature_names };
+                trees.push(Tree { root, provenance, weight: Tree::default_weight() });
+            }
+        }
+        self.rng = rng;
+        let first_tree = self.trees.len();
+        self.trees.append(&mut trees);
+        self.normalize_tree_weights();
+        self.update_tree_weights(first_tree);
+        self.update_threshold();
+    }
+
+    /// Returns the range of each feature over the given rows, leaving out features none of the rows have.
+    fn tree_ranges(&self, rows: &[usize], feature_names: &[String]) -> HashMap<String, (f64, f64)> {
+        let node_data = NodeData::new(&self.training_data, rows, feature_names, 0);
+        feature_names.iter().filter_map(|feature_name| {
+            let values = node_data.values(feature_name);
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            if min <= max { Some((feature_name.clone(), (min, max))) } else { None }
+        }).collect()
+    }
 
-        // Randomly select a split value.
-        let feature_value_set = &feature_values[unwrapped_feature_name];
-        let feature_value_set_len = feature_value_set.len();
-        if feature_value_set_len <= 1 {
+    /// Randomly selects the features a tree may split on, or returns None if it may use all of them.
+    fn draw_feature_names(&self, feature_names: &[String], rng: &mut StdRng) -> Option<Vec<String>> {
+        if self.max_features == 0 || self.max_features >= feature_names.len() {
             return None;
         }
-        let split_value_index = rand::rng().random_range(0..feature_value_set_len);
-        let split_value = feature_value_set[split_value_index];
 
-        // Create a tree node to hold the split value.
-        let mut tree_root = Node::new(unwrapped_feature_name, split_value);
+        let mut indexes = rand::seq::index::sample(rng, feature_names.len(), self.max_features).into_vec();
+        indexes.sort_unstable();
+        Some(indexes.into_iter().map(|i| feature_names[i].clone()).collect())
+    }
 
-        // Create two versions of the feature value set that we just used,
-        // one for the left side of the tree and one for the right.
-        let mut temp_feature_values = feature_values.clone();
-        let (left_features, right_features) = feature_value_set.split_at(split_value_index);
+    /// Finds the threshold above which the contamination fraction of the training samples score.
+    fn update_threshold(&mut self) {
+        let Some(contamination) = self.contamination else {
+            return;
+        };
+        let num_rows = self.training_data.num_rows();
+        if num_rows == 0 || self.trees.is_empty() {
+            return;
+        }
 
-        // Create the left subtree.
-        temp_feature_values.insert(unwrapped_feature_name.to_string(), left_features.to_vec());
-        tree_root.left = self.create_tree(temp_feature_values.clone(), depth + 1);
+        let mut scores = self.training_scores();
+        scores.sort_unstable_by(|a, b| b.total_cmp(a));
+        let num_anomalies = ((num_rows as f64 * contamination).ceil() as usize).clamp(1, num_rows);
+        self.threshold = Some(scores[num_anomalies - 1]);
+    }
 
-        // Create the right subtree.
-        if right_features.len() > 0 {
-            temp_feature_values.insert(unwrapped_feature_name.to_string(), right_features.to_vec());
-            tree_root.right = self.create_tree(temp_feature_values.clone(), depth + 1);
+    /// Weights the trees by how much sooner they isolate the training rows labeled as anomalies than the normal
+    /// rows: those labeled normal or, if there are none, up to MAX_WEIGHTING_ROWS of the unlabeled rows. The
+    /// weights are exp(z), where z is a tree's standardized separation among all of the trees, scaled to average 1.
+    /// Only the trees from first_tree on are weighted, so the older trees keep their weights, including any feedback,
+    /// relative to each other. Without any labeled anomalies the weights are left alone.
+    fn update_tree_weights(&mut self, first_tree: usize) {
+        let num_rows = self.training_data.num_rows();
+        let anomalous_rows: Vec<usize> = (0..num_rows).filter(|&row| self.training_data.label(row) == Some(true)).collect();
+        if anomalous_rows.is_empty() || self.trees.len() < 2 {
+            return;
+        }
+        let mut normal_rows: Vec<usize> = (0..num_rows).filter(|&row| self.training_data.label(row) == Some(false)).collect();
+        if normal_rows.is_empty() {
+            let unlabeled_rows: Vec<usize> = (0..num_rows).filter(|&row| self.training_data.label(row).is_none()).collect();
+            let stride = unlabeled_rows.len().div_ceil(MAX_WEIGHTING_ROWS).max(1);
+            normal_rows = unlabeled_rows.into_iter().step_by(stride).collect();
+        }
+        if normal_rows.is_empty() {
+            return;
         }
 
-        let tree = Some(Box::new(tree_root));
-        tree
+        let mean_depth = |tree: &Tree, rows: &[usize]| {
+            rows.iter().map(|&row| self.score_tree(&|feature_name: &str| self.training_data.value(feature_name, row), &tree.root)).sum::<f64>() / rows.len() as f64
+        };
+        let separations: Float64Vec = self.trees.iter().map(|tree| mean_depth(tree, &normal_rows) - mean_depth(tree, &anomalous_rows)).collect();
+        let mean = separations.iter().sum::<f64>() / separations.len() as f64;
+        let std = (separations.iter().map(|separation| (separation - mean).powi(2)).sum::<f64>() / separations.len() as f64).sqrt();
+        let weights: Float64Vec = separations.iter().map(|separation| if std > 0.0 { ((separation - mean) / std).exp() } else { 1.0 }).collect();
+        let mean_weight = weights.iter().sum::<f64>() / weights.len() as f64;
+        for (tree, weight) in self.trees.iter_mut().zip(weights).skip(first_tree) {
+            tree.weight = weight / mean_weight;
+        }
+        self.normalize_tree_weights();
     }
 
-    /// Creates a forest containing the number of trees specified to the constructor.
-    pub fn create(&mut self) {
-    	for _i in 0..self.num_trees_to_create {
-            let temp_feature_values = self.feature_values.clone();
-            let tree = self.create_tree(temp_feature_values, 0);
-            if tree.is_some() {
-                self.trees.push(tree.unwrap());
+    /// Scales the tree weights to average 1, keeping their ratios.
+    fn normalize_tree_weights(&mut self) {
+        let mean_weight = self.trees.iter().map(|tree| tree.weight).sum::<f64>() / self.trees.len() as f64;
+        if mean_weight > 0.0 && mean_weight.is_finite() {
+            for tree in &mut self.trees {
+                tree.weight /= mean_weight;
             }
         }
     }
 
-    /// Scores the sample against the specified tree.
-    fn score_tree(&self, sample: &Sample, tree: &NodeBox) -> f64 {
-        let mut depth = 0.0;
-        let mut current_node = tree;
-        let mut done = false;
-
-        while !done {
-            let mut found_feature = false;
-
-            for current_feature in &sample.features {
-
-                // If the current node has the feature in question.
-                if current_feature.name == current_node.feature_name {
-					if current_feature.value < current_node.split_value {
-                        match current_node.left {
-                            None => {
-                                done = true;
-                            }
-                            Some(ref next_node) => {
-                                current_node = next_node;
-                            }
-                        }
-                    } else {
-                        match current_node.right {
-                            None => {
-                                done = true;
-                            }
-                            Some(ref next_node) => {
-                                current_node = next_node;
-                            }
-                        }
-                    }
+    /// Returns each tree's weight in the score, oldest tree first. Weights average 1, including after trees are
+    /// added, pruned or merged, and are only changed from 1 by labeled training samples and by feedback.
+    pub fn tree_weights(&self) -> Float64Vec {
+        self.trees.iter().map(|tree| tree.weight).collect()
+    }
 
-                    depth = depth + 1.0;
-                    found_feature = true;
-                }
+    /// Learns from an analyst's verdict on a scored sample without rebuilding any trees, as in Active Anomaly
+    /// Discovery. Each tree's weight is multiplied by exp(rate * z) for a true anomaly, or exp(-rate * z) for a
+    /// benign sample, where z is how much shorter the sample's path length is in that tree than its average across
+    /// the trees, in standard deviations. The weights are then scaled to average 1 and the threshold is updated.
+    /// Feedback is kept when trees are added, but lost when `create` rebuilds them all.
+    pub fn add_feedback(&mut self, sample: &Sample, is_anomaly: bool) {
+        let depths = self.tree_depths(sample);
+        if depths.len() < 2 {
+            return;
+        }
+        let mean = depths.iter().sum::<f64>() / depths.len() as f64;
+        let std = (depths.iter().map(|depth| (depth - mean).powi(2)).sum::<f64>() / depths.len() as f64).sqrt();
+        if std == 0.0 {
+            return;
+        }
+
+        let sign = if is_anomaly { 1.0 } else { -1.0 };
+        for (tree, depth) in self.trees.iter_mut().zip(&depths) {
+            tree.weight *= (sign * FEEDBACK_LEARNING_RATE * (mean - depth) / std).exp();
+        }
+        self.normalize_tree_weights();
+        self.update_threshold();
+    }
+
+    /// Returns the normalized score of each training row, in row order. Loaded forests have no training rows.
+    pub(crate) fn training_scores(&self) -> Float64Vec {
+        (0..self.training_data.num_rows()).map(|row| {
+            self.normalized_score_with(&|feature_name: &str| self.training_data.value(feature_name, row))
+        }).collect()
+    }
+
+    /// Creates a forest containing the number of trees specified to the constructor, replacing any existing trees.
+    pub fn create(&mut self) {
+        self.trees.clear();
+        self.build_trees(0, self.num_trees_to_create);
+        self.num_trained_samples = self.training_data.num_rows();
+    }
+
+    /// Adds up to the specified number of trees, built from the samples that were added since the forest was last
+    /// created or grown. If there are no new samples, the trees are built from all of the training samples.
+    pub fn grow(&mut self, num_trees: u32) {
+        let first_sample = if self.num_trained_samples < self.training_data.num_rows() { self.num_trained_samples } else { 0 };
+        self.build_trees(first_sample, num_trees);
+        self.num_trees_to_create += num_trees;
+        self.num_trained_samples = self.training_data.num_rows();
+    }
+
+    /// Removes up to the specified number of trees, oldest first. The remaining trees keep their relative weights.
+    pub fn prune_oldest(&mut self, num_trees: usize) {
+        let num_trees = num_trees.min(self.trees.len());
+        self.trees.drain(..num_trees);
+        self.normalize_tree_weights();
+        self.num_trees_to_create = self.num_trees_to_create.saturating_sub(num_trees as u32);
+        self.update_threshold();
+    }
+
+    /// Returns the names of the features seen during training, sorted.
+    pub fn feature_names(&self) -> Vec<String> {
+        let mut feature_names: Vec<String> = self.feature_values.keys().cloned().collect();
+        feature_names.sort();
+        feature_names
+    }
+
+    /// Returns the smallest and largest training values of the feature, or None if it has none. Training values are
+    /// added when the trees are built.
+    pub fn feature_range(&self, feature_name: &str) -> Option<(f64, f64)> {
+        let values = self.feature_values.get(feature_name)?;
+        Some((*values.first()?, *values.last()?))
+    }
+
+    /// Returns every training value of the feature, sorted, or None if the feature is unknown. Training values are
+    /// added when the trees are built.
+    pub fn training_values(&self, feature_name: &str) -> Option<&[f64]> {
+        self.feature_values.get(feature_name).map(|values| values.as_slice())
+    }
+
+    /// Moves the trees and training data of another forest, trained on the same features, into this one.
+    /// Trees keep their provenance and remain ordered oldest first.
+    pub fn merge(&mut self, mut other: Forest) -> Result<(), ForestError> {
+        let expected = self.feature_names();
+        let found = other.feature_names();
+        if !self.feature_values.is_empty() && expected != found {
+            return Err(ForestError::SchemaMismatch { expected, found });
+        }
+
+        // Rows are reordered below, so bring both forests' feature values up to date first.
+        self.update_feature_values();
+        other.update_feature_values();
+        for (feature_name, values) in other.feature_values {
+            let feature_value_set = self.feature_values.entry(feature_name).or_default();
+            feature_value_set.extend(values);
+            feature_value_set.sort_unstable_by(f64::total_cmp);
+        }
+
+        // The other forest's samples have already been used to build its trees.
+        let new_rows = self.training_data.split_off(self.num_trained_samples);
+        self.training_data.append(other.training_data);
+        self.num_trained_samples = self.training_data.num_rows();
+        self.training_data.append(new_rows);
+        self.num_valued_samples = self.training_data.num_rows();
+
+        self.trees.extend(other.trees);
+        self.trees.sort_by_key(|tree| tree.provenance.created);
+        self.normalize_tree_weights();
+        self.num_trees_to_create += other.num_trees_to_create;
+        self.update_threshold();
+        Ok(())
+    }
+
+    /// Walks the tree, returning its depth and node count and tallying the features used at each split.
+    fn tree_stats(tree: &NodeBox, feature_usage: &mut HashMap<String, usize>) -> (usize, usize) {
+        if tree.hyperplane.is_empty() {
+            *feature_usage.entry(tree.feature_name.clone()).or_insert(0) += 1;
+        }
+        for (feature_name, _) in &tree.hyperplane {
+            *feature_usage.entry(feature_name.clone()).or_insert(0) += 1;
+        }
+
+        let mut depth = 0;
+        let mut node_count = 1;
+        for child in [&tree.left, &tree.right].iter().copied().flatten() {
+            let (child_depth, child_node_count) = Forest::tree_stats(child, feature_usage);
+            depth = depth.max(child_depth);
+            node_count += child_node_count;
+        }
+        (depth + 1, node_count)
+    }
+
+    /// Describes the trees that were built and flags conditions that are likely to produce meaningless scores.
+    pub fn stats(&self) -> ForestStats {
+        let mut feature_usage = HashMap::new();
+        let mut tree_depths = Vec::with_capacity(self.trees.len());
+        let mut tree_node_counts = Vec::with_capacity(self.trees.len());
+
+        for tree in &self.trees {
+            let (depth, node_count) = Forest::tree_stats(&tree.root, &mut feature_usage);
+            tree_depths.push(depth);
+            tree_node_counts.push(node_count);
+        }
+
+        let mut warnings = Vec::new();
+        let num_trees_built = self.trees.len();
+        if self.feature_values.is_empty() {
+            warnings.push(ForestWarning::NoTrainingData);
+        }
+        if num_trees_built == 0 && self.num_trees_to_create > 0 {
+            warnings.push(ForestWarning::NoTreesBuilt);
+        }
+        else if num_trees_built > 0 && num_trees_built < self.num_trees_to_create as usize {
+            warnings.push(ForestWarning::TreesDropped { requested: self.num_trees_to_create, built: num_trees_built });
+        }
+
+        let mut feature_names: Vec<&String> = self.feature_values.keys().collect();
+        feature_names.sort();
+        for feature_name in feature_names {
+            let values = &self.feature_values[feature_name];
+            if values.len() > 1 && values.first() == values.last() {
+                warnings.push(ForestWarning::ConstantFeature(feature_name.clone()));
+            }
+            if num_trees_built > 0 && !feature_usage.contains_key(feature_name) {
+                warnings.push(ForestWarning::UnusedFeature(feature_name.clone()));
             }
+        }
 
-			// If the tree contained a feature not in the sample then take
-			// both sides of the tree and average the scores together.
-			if found_feature == false {
-                let left_tree = &current_node.left;
-                let right_tree = &current_node.right;
-                let mut left_depth = depth;
-                let mut right_depth = depth;
+        ForestStats {
+            num_trees_requested: self.num_trees_to_create,
+            num_trees_built,
+            depth: Distribution::from_values(&tree_depths),
+            node_count: Distribution::from_values(&tree_node_counts),
+            tree_depths,
+            tree_node_counts,
+            feature_usage,
+            tree_feature_names: self.trees.iter().map(|tree| tree.provenance.feature_names.clone()).collect(),
+            warnings,
+        }
+    }
+
+    /// Scores a sample against the specified tree. The sample is given as a function that looks up
+    /// the value of a feature by name, returning None if the sample does not have the feature.
+    fn score_tree<F: Fn(&str) -> Option<f64> + ?Sized>(&self, feature_value: &F, tree: &NodeBox) -> f64 {
+        let mut depth = 0.0;
+        let mut current_node = tree;
 
-                match left_tree {
-                    &None => {
+        loop {
+            match self.node_value(feature_value, current_node) {
+                Some(value) => {
+                    if current_node.range.is_some_and(|(min, max)| value < min || value > max) {
+                        return depth + 1.0;
                     }
-                    &Some(ref left_tree) => {
-        			    left_depth = left_depth + self.score_tree(sample, left_tree);
+                    let (next_node, leaf_size) = if value < current_node.split_value {
+                        (&current_node.left, current_node.left_size)
+                    } else {
+                        (&current_node.right, current_node.right_size)
+                    };
+
+                    depth += 1.0;
+                    match next_node {
+                        None => {
+                            // Leaves cut off before isolating their rows get the average path length of the subtree
+                            // that would have been built from them.
+                            return depth + c(leaf_size);
+                        }
+                        Some(ref next_node) => {
+                            current_node = next_node;
+                        }
                     }
                 }
-                match right_tree {
-                    &None => {
-                    }
-                    &Some(ref right_tree) => {
-        			    right_depth = right_depth + self.score_tree(sample, right_tree);
-                    }
+                None => {
+			        // If the tree contained a feature not in the sample then take
+			        // both sides of the tree and average the scores together.
+                    let left_depth = depth + match &current_node.left {
+                        Some(left_tree) => self.score_tree(feature_value, left_tree),
+                        None => c(current_node.left_size),
+                    };
+                    let right_depth = depth + match &current_node.right {
+                        Some(right_tree) => self.score_tree(feature_value, right_tree),
+                        None => c(current_node.right_size),
+                    };
+                    return (left_depth + right_depth) / 2.0;
                 }
-				depth = (left_depth + right_depth) / 2.0;
-                return depth;
             }
         }
-        depth
     }
 
-    /// Scores the sample against the entire forest of trees. Result is the average path length.
-    pub fn score(&self, sample: &Sample) -> f64 {
-        let mut score = 0.0;
+    /// Returns the value the node compares against its split value, which is either the value of a feature or its
+    /// projection onto the node's hyperplane. Returns None if a feature is missing and the policy doesn't impute it.
+    fn node_value<F: Fn(&str) -> Option<f64> + ?Sized>(&self, feature_value: &F, node: &Node) -> Option<f64> {
+        if node.hyperplane.is_empty() {
+            return feature_value(&node.feature_name).or_else(|| self.impute(&node.feature_name));
+        }
+        node.hyperplane.iter().map(|(feature_name, coefficient)| {
+            feature_value(feature_name).or_else(|| self.impute(feature_name)).map(|value| coefficient * value)
+        }).sum()
+    }
 
-        if self.trees.len() > 0 {
-            for tree in &self.trees {
-                score += self.score_tree(sample, tree) as f64;
+    /// Returns the value to use for a missing feature, according to the missing value policy.
+    fn impute(&self, feature_name: &str) -> Option<f64> {
+        match self.missing_value_policy {
+            MissingValuePolicy::AverageBranches => None,
+            MissingValuePolicy::ImputeMedian => {
+                self.feature_values.get(feature_name).and_then(|values| values.get(values.len() / 2)).copied()
             }
-            score /= self.trees.len() as f64;
         }
-        score
     }
 
-    fn h(&self, i: usize) -> f64 {
-        let result = (i as f64).ln() + 0.5772156649;
-        result
+    /// Computes the path length in each of the trees.
+    pub(crate) fn tree_depths_with<F: Fn(&str) -> Option<f64> + ?Sized>(&self, feature_value: &F) -> Float64Vec {
+        self.trees.iter().map(|tree| self.score_tree(feature_value, &tree.root)).collect()
     }
 
-    fn c(&self, n: usize) -> f64 {
-        let result = 2.0 * self.h(n - 1) - (2 * (n - 1) / n) as f64;
-        result
+    /// Combines the path lengths across all of the trees, using the forest's aggregator.
+    pub(crate) fn score_with<F: Fn(&str) -> Option<f64> + ?Sized>(&self, feature_value: &F) -> f64 {
+        let weights: Float64Vec = self.trees.iter().map(|tree| tree.weight).collect();
+        self.aggregator.aggregate_weighted(&self.tree_depths_with(feature_value), &weights)
     }
 
-    /// Scores the sample against the entire forest of trees. Result is normalized so that values
-    /// close to 1 indicate anomalies and values close to zero indicate normal values."""
-    pub fn normalized_score(&self, sample: &Sample) -> f64 {
+    /// Returns the sample's path length in each tree, oldest tree first.
+    pub fn tree_depths(&self, sample: &Sample) -> Float64Vec {
+        self.tree_depths_with(&|feature_name: &str| sample.feature_value(feature_name))
+    }
+
+    /// Scores the sample against the entire forest of trees. Result is the path length, combined across the
+    /// trees by the forest's aggregator, which defaults to the average.
+    pub fn score(&self, sample: &Sample) -> f64 {
+        self.score_with(&|feature_name: &str| sample.feature_value(feature_name))
+    }
+
+    /// Returns the average path length of an unsuccessful search in a tree built from the same number of samples
+    /// as the trees in the forest, which is used to normalize path lengths into scores.
+    fn average_path_length(&self) -> f64 {
+        let num_samples = self.trees.iter().map(|tree| tree.provenance.num_samples).sum::<usize>() / self.trees.len().max(1);
+        c(num_samples).max(1.0)
+    }
+
+    /// Computes the normalized score, per the original paper, from the combined path length across all of the trees.
+    pub(crate) fn normalized_score_with<F: Fn(&str) -> Option<f64> + ?Sized>(&self, feature_value: &F) -> f64 {
         let mut score = 0.0;
-        let mut avg_path_len = 0.0;
         let num_trees = self.trees.len();
 
         // Compute the average path length for all valid trees.
         if num_trees > 0 {
-            for tree in &self.trees {
-                avg_path_len += self.score_tree(sample, tree) as f64;
-            }
-            avg_path_len /= self.trees.len() as f64;
-
-            let exponent = -1.0 * (avg_path_len / self.c(num_trees));
+            let avg_path_len = self.score_with(feature_value);
+            let exponent = -(avg_path_len / self.average_path_length());
             let x = 2.0_f64;
             score = x.powf(exponent);
         }
-
-        // Normalize, per the original paper.
         score
     }
 
+    /// Scores the sample against the entire forest of trees. Result is normalized so that values
+    /// close to 1 indicate anomalies and values close to zero indicate normal values.
+    pub fn normalized_score(&self, sample: &Sample) -> f64 {
+        self.normalized_score_with(&|feature_name: &str| sample.feature_value(feature_name))
+    }
+
+    /// Returns true if the sample's normalized score is at or above the forest's threshold, or above 0.5 if the
+    /// forest has no threshold.
+    pub fn is_anomaly(&self, sample: &Sample) -> bool {
+        let score = self.normalized_score(sample);
+        match self.threshold {
+            Some(threshold) => score >= threshold,
+            None => score > 0.5,
+        }
+    }
+
+    /// Returns the calibrated probability that the sample is an outlier, or None if the forest is not calibrated.
+    pub fn probability(&self, sample: &Sample) -> Option<f64> {
+        let calibration = self.calibration.as_ref()?;
+        Some(calibration.probability(self.normalized_score(sample)))
+    }
+
+    /// Computes the normalized score along with its uncertainty. The confidence interval is a normal approximation
+    /// built from the standard error of the trees' path lengths, so it narrows as trees are added. Trees count in
+    /// proportion to their weights, with the effective number of trees used in place of the number of trees.
+    pub(crate) fn score_with_uncertainty_with<F: Fn(&str) -> Option<f64> + ?Sized>(&self, feature_value: &F, confidence: f64) -> ScoreUncertainty {
+        let depths = self.tree_depths_with(feature_value);
+        let num_trees = depths.len();
+        if num_trees == 0 {
+            return ScoreUncertainty::default();
+        }
+
+        let c = self.average_path_length();
+        let normalize = |depth: f64| 2.0_f64.powf(-(depth / c));
+        let weights: Float64Vec = self.trees.iter().map(|tree| tree.weight).collect();
+        let depth = self.aggregator.aggregate_weighted(&depths, &weights);
+
+        // The weighted variance, corrected for bias using the effective number of trees, which equals the number of
+        // trees when the weights are equal.
+        let total_weight = weights.iter().sum::<f64>();
+        let num_effective_trees = total_weight * total_weight / weights.iter().map(|w| w * w).sum::<f64>();
+        let weighted_variance = |values: &[f64]| {
+            let mean = values.iter().zip(&weights).map(|(v, w)| v * w).sum::<f64>() / total_weight;
+            let variance = values.iter().zip(&weights).map(|(v, w)| w * (v - mean) * (v - mean)).sum::<f64>() / total_weight;
+            if num_effective_trees > 1.0 { variance * num_effective_trees / (num_effective_trees - 1.0) } else { variance }
+        };
+
+        let depth_variance = weighted_variance(&depths);
+        let margin = normal_quantile(0.5 + confidence / 2.0) * (depth_variance / num_effective_trees).sqrt();
+
+        let scores: Float64Vec = depths.iter().map(|d| normalize(*d)).collect();
+        let score_variance = weighted_variance(&scores);
+
+        // Longer paths mean lower scores, so the bounds swap.
+        ScoreUncertainty { mean: normalize(depth), std: score_variance.sqrt(), lower: normalize(depth + margin), upper: normalize(depth - margin) }
+    }
+
+    /// Scores the sample against the entire forest of trees, as with `normalized_score`, and reports how much the
+    /// score varies across the trees along with a confidence interval at the given level, such as 0.95.
+    pub fn score_with_uncertainty(&self, sample: &Sample, confidence: f64) -> ScoreUncertainty {
+        assert!(confidence > 0.0 && confidence < 1.0, "The confidence level must be between 0 and 1");
+        self.score_with_uncertainty_with(&|feature_name: &str| sample.feature_value(feature_name), confidence)
+    }
+
+    /// Serializes the forest to JSON.
     pub fn dump(&self) -> String {
-        let json_str = serde_json::to_string(&self).unwrap();
-        json_str
+        serde_json::to_string(&self).unwrap()
+    }
+
+    /// Creates a forest from JSON produced by `dump`.
+    pub fn load(json_str: &str) -> Result<Forest, ForestError> {
+        let mut forest: Forest = serde_json::from_str(json_str).map_err(|e| ForestError::ParseError(e.to_string()))?;
+        forest.restore_seed();
+        Ok(forest)
     }
 
-    pub fn load(&self, json_str: &String) {
-        let obj = serde_json::from_str(json_str).unwrap();
-        obj
+    /// Reseeds the random number generator of a deserialized forest, so that trees it grows can be reproduced.
+    pub(crate) fn restore_seed(&mut self) {
+        if let Some(seed) = self.seed {
+            self.set_seed(seed);
+        }
     }
 }
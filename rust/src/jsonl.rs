@@ -0,0 +1,67 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Scoring newline-delimited JSON samples, for use in shell pipelines.
+
+extern crate serde_json;
+
+use std::io::{BufRead, Write};
+use crate::isolation_forest::{Forest, ForestError, Sample};
+
+/// Reads newline-delimited JSON samples, in the same shape as a serialized `Sample`, scores each one against the
+/// forest, and writes it back out with `score` and `normalized_score` fields added. Fields that are not part of a
+/// sample are passed through unchanged and blank lines are skipped.
+///
+/// Samples are processed one line at a time, so memory use does not grow with the input, and each output line is
+/// flushed as it is written, so a slow consumer slows down reading rather than causing output to be buffered.
+/// Returns the number of samples that were scored.
+pub fn score_json_lines<R: BufRead, W: Write>(forest: &Forest, mut reader: R, mut writer: W) -> Result<usize, ForestError> {
+    let mut line = String::new();
+    let mut line_num = 0;
+    let mut num_scored = 0;
+
+    loop {
+        line.clear();
+        let num_bytes = reader.read_line(&mut line).map_err(|e| ForestError::ReadError(e.to_string()))?;
+        if num_bytes == 0 {
+            break;
+        }
+        line_num += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parse_error = |e: serde_json::Error| ForestError::ParseError(format!("Line {}: {}", line_num, e));
+        let mut value: serde_json::Value = serde_json::from_str(&line).map_err(parse_error)?;
+        let sample: Sample = serde_json::from_value(value.clone()).map_err(parse_error)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert("score".to_string(), forest.score(&sample).into());
+            object.insert("normalized_score".to_string(), forest.normalized_score(&sample).into());
+        }
+
+        serde_json::to_writer(&mut writer, &value).map_err(|e| ForestError::WriteError(e.to_string()))?;
+        writer.write_all(b"\n").and_then(|_| writer.flush()).map_err(|e| ForestError::WriteError(e.to_string()))?;
+        num_scored += 1;
+    }
+    Ok(num_scored)
+}
@@ -21,6 +21,24 @@
 //	SOFTWARE.
 
 pub mod isolation_forest;
+pub mod active_learning;
+pub mod builder;
+pub mod calibration;
+pub mod drift;
+pub mod half_space_trees;
+pub mod jsonl;
+pub mod metrics;
+pub mod segmented;
+pub mod timeseries;
+pub mod tuning;
+pub mod splitter;
+mod training_data;
+#[cfg(feature = "ndarray")]
+pub mod array;
+#[cfg(feature = "arrow")]
+pub mod record_batch;
+#[cfg(feature = "polars")]
+pub mod dataframe;
 
 #[cfg(test)]
 mod tests {
@@ -37,6 +55,12 @@ mod tests {
         let mut forest = crate::isolation_forest::Forest::new(10, 10);
         let mut dump = false;
 
+        use rand::SeedableRng;
+        forest.set_seed(26);
+
+        // Seeded, so the scores are repeatable.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(26);
+
         for arg in args {
             if arg == "--dump" {
                 dump = true;
@@ -45,8 +69,8 @@ mod tests {
 
         // Training samples.
         for _i in 0..num_training_samples {
-            let x = rand::rng().random_range(0..25) as u64;
-            let y = rand::rng().random_range(15..45) as u64;
+            let x = rng.random_range(0..25) as f64;
+            let y = rng.random_range(15..45) as f64;
 
             let mut features = crate::isolation_forest::FeatureList::new();
             features.push(crate::isolation_forest::Feature::new("x", x));
@@ -66,8 +90,8 @@ mod tests {
         let mut avg_control_score = 0.0;
         let mut avg_control_normalized_score = 0.0;
         for i in 0..num_tests {
-            let x = rand::rng().random_range(0..25) as u64;
-            let y = rand::rng().random_range(15..45) as u64;
+            let x = rng.random_range(0..25) as f64;
+            let y = rng.random_range(15..45) as f64;
 
             let mut features = crate::isolation_forest::FeatureList::new();
             features.push(crate::isolation_forest::Feature::new("x", x));
@@ -94,8 +118,8 @@ mod tests {
         let mut avg_outlier_score = 0.0;
         let mut avg_outlier_normalized_score = 0.0;
         for i in 0..num_tests {
-            let x = rand::rng().random_range(0..25) as u64;
-            let y = rand::rng().random_range(15..45) as u64;
+            let x = rng.random_range(25..50) as f64;
+            let y = rng.random_range(45..70) as f64;
 
             let mut features = crate::isolation_forest::FeatureList::new();
             features.push(crate::isolation_forest::Feature::new("x", x));
@@ -121,6 +145,1085 @@ mod tests {
         }
 
         assert!(avg_control_normalized_score < avg_outlier_normalized_score);
+
+        // Integer feature values, as used by earlier versions, are still accepted.
+        let mut features = vec![crate::isolation_forest::Feature::new("x", 10_u64), crate::isolation_forest::Feature::new("y", 30)];
+        let mut sample = crate::isolation_forest::Sample::new("integer");
+        sample.add_features(&mut features);
+        assert_eq!(sample.feature_value("x"), Some(10.0));
+        assert_eq!(forest.score(&sample), forest.score(&make_sample("float", &[("x", 10.0), ("y", 30.0)])));
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut forest = crate::isolation_forest::Forest::new(10, 10);
+        let mut empty_forest = crate::isolation_forest::Forest::new(10, 10);
+
+        for _i in 0..50 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+
+            let mut features = vec![
+                crate::isolation_forest::Feature::new("x", x),
+                crate::isolation_forest::Feature::new("y", y),
+            ];
+            let mut sample = crate::isolation_forest::Sample::new("training");
+            sample.add_features(&mut features);
+            forest.add_sample(sample);
+        }
+
+        forest.create();
+        empty_forest.create();
+
+        let stats = forest.stats();
+        assert_eq!(stats.num_trees_requested, 10);
+        assert_eq!(stats.num_trees_built, 10);
+        assert_eq!(stats.tree_depths.len(), 10);
+        assert!(stats.depth.min >= 1 && stats.depth.max <= 10);
+        assert_eq!(stats.feature_usage.values().sum::<usize>(), stats.tree_node_counts.iter().sum::<usize>());
+        assert!(!stats.warnings.contains(&crate::isolation_forest::ForestWarning::NoTreesBuilt));
+
+        let stats = empty_forest.stats();
+        assert_eq!(stats.num_trees_built, 0);
+        assert!(stats.warnings.contains(&crate::isolation_forest::ForestWarning::NoTrainingData));
+        assert!(stats.warnings.contains(&crate::isolation_forest::ForestWarning::NoTreesBuilt));
+    }
+
+    #[test]
+    fn single_feature_test() {
+        use rand::SeedableRng;
+
+        let mut forest = crate::isolation_forest::Forest::new(100, 10);
+        forest.set_seed(27);
+
+        // A univariate series, such as a single metric sampled over time. Seeded, so the scores are repeatable.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(27);
+        for _i in 0..200 {
+            let value = rng.random_range(40..60) as f64;
+
+            let mut features = vec![crate::isolation_forest::Feature::new("value", value)];
+            let mut sample = crate::isolation_forest::Sample::new("training");
+            sample.add_features(&mut features);
+            forest.add_sample(sample);
+        }
+
+        forest.create();
+        assert_eq!(forest.stats().num_trees_built, 100);
+
+        let mut features = vec![crate::isolation_forest::Feature::new("value", 50.0)];
+        let mut normal_sample = crate::isolation_forest::Sample::new("normal");
+        normal_sample.add_features(&mut features);
+
+        let mut features = vec![crate::isolation_forest::Feature::new("value", 500.0)];
+        let mut outlier_sample = crate::isolation_forest::Sample::new("outlier");
+        outlier_sample.add_features(&mut features);
+
+        let normal_score = forest.normalized_score(&normal_sample);
+        let outlier_score = forest.normalized_score(&outlier_sample);
+        println!("Normal: {:.2} Outlier: {:.2}", normal_score, outlier_score);
+
+        assert!(normal_score < 0.5);
+        assert!(outlier_score > 0.8);
+    }
+
+    fn make_sample(name: &str, values: &[(&str, f64)]) -> crate::isolation_forest::Sample {
+        let mut features = crate::isolation_forest::FeatureList::new();
+        for (feature_name, value) in values {
+            features.push(crate::isolation_forest::Feature::new(feature_name, *value));
+        }
+
+        let mut sample = crate::isolation_forest::Sample::new(name);
+        sample.add_features(&mut features);
+        sample
+    }
+
+    #[test]
+    fn incremental_training_test() {
+        let mut forest = crate::isolation_forest::Forest::new(10, 10);
+        let mut other_forest = crate::isolation_forest::Forest::new(4, 10);
+        let mut mismatched_forest = crate::isolation_forest::Forest::new(4, 10);
+        forest.set_origin("us-east");
+        other_forest.set_origin("eu-west");
+
+        for _i in 0..50 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+            forest.add_sample(make_sample("training", &[("x", x), ("y", y)]));
+            other_forest.add_sample(make_sample("training", &[("x", x), ("y", y)]));
+            mismatched_forest.add_sample(make_sample("training", &[("x", x), ("z", y)]));
+        }
+
+        forest.create();
+        other_forest.create();
+        mismatched_forest.create();
+        assert_eq!(forest.num_trees(), 10);
+
+        // Grow the forest using only the new samples.
+        for _i in 0..20 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+            forest.add_sample(make_sample("training", &[("x", x), ("y", y)]));
+        }
+        forest.grow(5);
+        assert_eq!(forest.num_trees(), 15);
+        assert!(forest.provenance()[10..].iter().all(|provenance| provenance.num_samples == 20));
+
+        // Pruning removes the oldest trees first.
+        forest.prune_oldest(10);
+        assert_eq!(forest.num_trees(), 5);
+        assert_eq!(forest.stats().num_trees_requested, 5);
+
+        // Merging keeps the provenance of both forests.
+        forest.merge(other_forest).unwrap();
+        assert_eq!(forest.num_trees(), 9);
+        let origins: Vec<&str> = forest.provenance().iter().map(|provenance| provenance.origin.as_str()).collect();
+        assert!(origins.contains(&"us-east"));
+        assert!(origins.contains(&"eu-west"));
+
+        match forest.merge(mismatched_forest) {
+            Err(crate::isolation_forest::ForestError::SchemaMismatch { .. }) => {}
+            _ => panic!("Expected a schema mismatch"),
+        }
+    }
+
+    #[test]
+    fn sample_weight_test() {
+        let mut forest = crate::isolation_forest::Forest::new(50, 10);
+
+        // Two equally sized clusters, one of which comes from a source we don't trust.
+        for _i in 0..100 {
+            let x = rand::rng().random_range(0.0..20.0);
+            forest.add_sample(make_sample("trusted", &[("x", x), ("y", x)]));
+
+            let x = rand::rng().random_range(80.0..100.0);
+            let mut sample = make_sample("noisy", &[("x", x), ("y", x)]);
+            sample.set_weight(0.001);
+            forest.add_sample(sample);
+        }
+
+        forest.create();
+
+        let trusted_score = forest.normalized_score(&make_sample("trusted", &[("x", 10.0), ("y", 10.0)]));
+        let noisy_score = forest.normalized_score(&make_sample("noisy", &[("x", 90.0), ("y", 90.0)]));
+        println!("Trusted: {:.2} Noisy: {:.2}", trusted_score, noisy_score);
+
+        assert!(trusted_score < noisy_score);
+    }
+
+    #[test]
+    fn sciforest_test() {
+        let mut forest = crate::isolation_forest::Forest::new(100, 10);
+        forest.set_splitter(crate::splitter::SCiForest { num_hyperplanes: 10, num_features: 2 });
+
+        // Normal data along a diagonal band, contaminated by a tight cluster of anomalies off to one side.
+        for _i in 0..250 {
+            let x = rand::rng().random_range(0.0..100.0);
+            let y = x + rand::rng().random_range(-5.0..5.0);
+            forest.add_sample(make_sample("normal", &[("x", x), ("y", y)]));
+        }
+        for _i in 0..10 {
+            let x = rand::rng().random_range(69.0..71.0);
+            let y = rand::rng().random_range(29.0..31.0);
+            forest.add_sample(make_sample("anomaly", &[("x", x), ("y", y)]));
+        }
+
+        forest.create();
+
+        let normal_score = forest.normalized_score(&make_sample("normal", &[("x", 50.0), ("y", 50.0)]));
+        let anomaly_score = forest.normalized_score(&make_sample("anomaly", &[("x", 70.0), ("y", 30.0)]));
+        println!("Normal: {:.2} Anomaly: {:.2}", normal_score, anomaly_score);
+        assert!(normal_score < anomaly_score);
+
+        // Hyperplane splits survive a round trip.
+        let loaded = crate::isolation_forest::Forest::load(&forest.dump()).unwrap();
+        let sample = make_sample("anomaly", &[("x", 70.0), ("y", 30.0)]);
+        assert_eq!(forest.score(&sample), loaded.score(&sample));
+        assert_eq!(forest.stats().feature_usage.len(), 2);
+    }
+
+    /// Always splits on x, halfway between its smallest and largest values.
+    struct MidrangeSplitter;
+
+    impl crate::splitter::Splitter for MidrangeSplitter {
+        fn split(&self, node: &crate::splitter::NodeData, _rng: &mut dyn rand::RngCore) -> Option<crate::splitter::Split> {
+            let values = node.values("x");
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            if min >= max {
+                return None;
+            }
+            Some(crate::splitter::Split::Feature { feature_name: "x".to_string(), split_value: (min + max) / 2.0 })
+        }
+
+        fn range_check(&self) -> bool {
+            true
+        }
+    }
+
+    /// Checks that a forest using the splitter scores an outlier higher than a sample from the training data.
+    fn check_splitter<S: crate::splitter::Splitter + 'static>(splitter: S) {
+        let mut forest = crate::isolation_forest::Forest::new(50, 10);
+        forest.set_splitter(splitter);
+
+        for _i in 0..200 {
+            let x = rand::rng().random_range(0.0..10.0);
+            let y = rand::rng().random_range(0.0..10.0);
+            forest.add_sample(make_sample("normal", &[("x", x), ("y", y)]));
+        }
+
+        forest.create();
+
+        let normal_score = forest.normalized_score(&make_sample("normal", &[("x", 5.0), ("y", 5.0)]));
+        let outlier_score = forest.normalized_score(&make_sample("outlier", &[("x", 50.0), ("y", 50.0)]));
+        println!("Normal: {:.2} Outlier: {:.2}", normal_score, outlier_score);
+        assert!(normal_score < outlier_score);
+    }
+
+    #[test]
+    fn splitter_test() {
+        check_splitter(crate::splitter::ValueUniform);
+        check_splitter(crate::splitter::RangeUniform);
+        check_splitter(crate::splitter::ExtendedHyperplane { extension_level: 1 });
+        check_splitter(crate::splitter::SCiForest { num_hyperplanes: 5, num_features: 2 });
+        check_splitter(crate::splitter::FairCut { num_hyperplanes: 5, num_features: 2 });
+        check_splitter(MidrangeSplitter);
+    }
+
+    #[test]
+    fn aggregator_test() {
+        use crate::isolation_forest::Aggregator;
+
+        assert_eq!(Aggregator::Mean.aggregate(&[1.0, 2.0, 6.0]), 3.0);
+        assert_eq!(Aggregator::Median.aggregate(&[6.0, 1.0, 2.0]), 2.0);
+        assert_eq!(Aggregator::Median.aggregate(&[6.0, 1.0, 2.0, 3.0]), 2.5);
+        assert_eq!(Aggregator::TrimmedMean(0.25).aggregate(&[100.0, 1.0, 2.0, 3.0]), 2.5);
+        assert_eq!(Aggregator::Min.aggregate(&[6.0, 1.0, 2.0]), 1.0);
+        assert_eq!(Aggregator::Median.aggregate(&[]), 0.0);
+
+        let mut forest = crate::isolation_forest::Forest::new(10, 10);
+        for _i in 0..100 {
+            let x = rand::rng().random_range(0.0..10.0);
+            forest.add_sample(make_sample("normal", &[("x", x)]));
+        }
+        forest.create();
+        forest.set_aggregator(Aggregator::Median);
+
+        let sample = make_sample("sample", &[("x", 5.0)]);
+        let depths = forest.tree_depths(&sample);
+        assert_eq!(depths.len(), forest.num_trees());
+        assert_eq!(forest.score(&sample), Aggregator::Median.aggregate(&depths));
+
+        let loaded_forest = crate::isolation_forest::Forest::load(&forest.dump()).unwrap();
+        assert_eq!(loaded_forest.aggregator(), Aggregator::Median);
+    }
+
+    #[test]
+    fn uncertainty_test() {
+        let mut forest = crate::isolation_forest::Forest::new(100, 10);
+        for _i in 0..200 {
+            let x = rand::rng().random_range(0.0..10.0);
+            let y = rand::rng().random_range(0.0..10.0);
+            forest.add_sample(make_sample("normal", &[("x", x), ("y", y)]));
+        }
+        forest.create();
+
+        // Samples beyond the training data are isolated at the root of every tree, so use one at the edge of it.
+        let sample = make_sample("edge", &[("x", 5.0), ("y", 9.0)]);
+        let uncertainty = forest.score_with_uncertainty(&sample, 0.95);
+        println!("{:?}", uncertainty);
+        assert_eq!(uncertainty.mean, forest.normalized_score(&sample));
+        assert!(uncertainty.std > 0.0);
+        assert!(uncertainty.lower < uncertainty.mean && uncertainty.mean < uncertainty.upper);
+
+        // A higher confidence level gives a wider interval.
+        let wider = forest.score_with_uncertainty(&sample, 0.99);
+        assert!(wider.lower < uncertainty.lower && uncertainty.upper < wider.upper);
+
+        let empty_forest = crate::isolation_forest::Forest::new(10, 10);
+        assert_eq!(empty_forest.score_with_uncertainty(&sample, 0.95).mean, 0.0);
+    }
+
+    #[test]
+    fn builder_test() {
+        use crate::isolation_forest::{Forest, ForestError, MaxSamples};
+        use rand::SeedableRng;
+
+        assert!(matches!(Forest::builder().num_trees(0).build(), Err(ForestError::InvalidParameter(_))));
+        assert!(matches!(Forest::builder().max_samples(MaxSamples::Fraction(1.5)).build(), Err(ForestError::InvalidParameter(_))));
+        assert!(matches!(Forest::builder().max_samples(MaxSamples::Count(16)).max_depth(20).build(), Err(ForestError::InvalidParameter(_))));
+        assert!(matches!(Forest::builder().contamination(0.7).build(), Err(ForestError::InvalidParameter(_))));
+
+        let mut samples = Vec::new();
+        for _i in 0..500 {
+            let x = rand::rng().random_range(0.0..10.0);
+            let y = rand::rng().random_range(0.0..10.0);
+            samples.push(make_sample("normal", &[("x", x), ("y", y)]));
+        }
+
+        // Forests built with the same seed from the same samples are identical.
+        let build = || {
+            let mut forest = Forest::builder().num_trees(50).max_samples(MaxSamples::Count(128)).bootstrap(true).seed(42).contamination(0.1).build().unwrap();
+            for sample in &samples {
+                forest.add_sample(sample.clone());
+            }
+            forest.create();
+            forest
+        };
+        let forest = build();
+        let other_forest = build();
+        let sample = make_sample("sample", &[("x", 5.0), ("y", 5.0)]);
+        assert_eq!(forest.score(&sample), other_forest.score(&sample));
+        assert!(forest.provenance().iter().all(|provenance| provenance.num_samples == 128));
+
+        // The threshold flags about the contamination fraction of the training samples.
+        let num_anomalies = samples.iter().filter(|sample| forest.is_anomaly(sample)).count();
+        println!("Threshold: {:?} Anomalies: {}", forest.threshold(), num_anomalies);
+        assert!((50..75).contains(&num_anomalies));
+
+        // Options are saved with the forest.
+        let loaded_forest = Forest::load(&forest.dump()).unwrap();
+        assert_eq!(loaded_forest.seed(), Some(42));
+        assert_eq!(loaded_forest.threshold(), forest.threshold());
+        assert!(loaded_forest.dump().contains("\"Max Samples\":{\"Count\":128}"));
+
+        // Without a contamination setting, a forest built with the defaults scores typical training samples below 0.5
+        // and flags a minority of them, from the edges of the data. Seeded, so the counts are repeatable.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let samples: Vec<_> = (0..500).map(|_| make_sample("normal", &[("x", rng.random_range(0.0..10.0)), ("y", rng.random_range(0.0..10.0))])).collect();
+        let mut default_forest = Forest::builder().seed(1).build().unwrap();
+        for sample in &samples {
+            default_forest.add_sample(sample.clone());
+        }
+        default_forest.create();
+        let num_anomalies = samples.iter().filter(|sample| default_forest.is_anomaly(sample)).count();
+        let mut scores: Vec<f64> = samples.iter().map(|sample| default_forest.normalized_score(sample)).collect();
+        scores.sort_unstable_by(|a, b| a.total_cmp(b));
+        println!("Median Score: {:.2} Default Anomalies: {}", scores[scores.len() / 2], num_anomalies);
+        assert!(scores[scores.len() / 2] < 0.5);
+        assert!(num_anomalies < samples.len() / 2);
+        let outlier = make_sample("outlier", &[("x", 50.0), ("y", 50.0)]);
+        assert!(default_forest.is_anomaly(&outlier));
+
+        // A single tree still produces meaningful scores.
+        let mut single_tree_forest = Forest::builder().num_trees(1).seed(1).build().unwrap();
+        for sample in &samples {
+            single_tree_forest.add_sample(sample.clone());
+        }
+        single_tree_forest.create();
+        assert!(single_tree_forest.normalized_score(&sample) < 1.0);
+        assert!(single_tree_forest.normalized_score(&sample) < single_tree_forest.normalized_score(&outlier));
+    }
+
+    #[test]
+    fn max_features_test() {
+        use crate::isolation_forest::Forest;
+
+        assert!(Forest::builder().max_features(0).build().is_err());
+
+        let mut forest = Forest::builder().num_trees(30).max_features(2).build().unwrap();
+        let feature_names = ["a", "b", "c", "d", "e"];
+        for _i in 0..100 {
+            let values: Vec<(&str, f64)> = feature_names.iter().map(|name| (*name, rand::rng().random_range(0.0..10.0))).collect();
+            forest.add_sample(make_sample("sample", &values));
+        }
+        forest.create();
+
+        // Each tree only splits on the features it drew.
+        let stats = forest.stats();
+        assert_eq!(stats.tree_feature_names.len(), forest.num_trees());
+        let mut drawn_feature_names = std::collections::HashSet::new();
+        for tree_feature_names in &stats.tree_feature_names {
+            assert_eq!(tree_feature_names.len(), 2);
+            drawn_feature_names.extend(tree_feature_names.iter().cloned());
+        }
+        assert!(stats.feature_usage.keys().all(|feature_name| drawn_feature_names.contains(feature_name)));
+
+        let loaded_forest = Forest::load(&forest.dump()).unwrap();
+        assert_eq!(loaded_forest.stats().tree_feature_names, stats.tree_feature_names);
+    }
+
+    #[test]
+    fn tuning_test() {
+        use crate::isolation_forest::MaxSamples;
+        use crate::tuning::{LabeledAuc, Search, SearchSpace, roc_auc, search};
+
+        assert_eq!(roc_auc(&[0.1, 0.2, 0.8, 0.9], &[false, false, true, true]), 1.0);
+        assert_eq!(roc_auc(&[0.9, 0.8, 0.2, 0.1], &[false, false, true, true]), 0.0);
+        assert_eq!(roc_auc(&[0.5, 0.5], &[false, true]), 0.5);
+
+        let random_sample = |name: &str, min: f64, max: f64| {
+            make_sample(name, &[("x", rand::rng().random_range(min..max)), ("y", rand::rng().random_range(min..max))])
+        };
+        let training_samples: Vec<_> = (0..300).map(|_| random_sample("normal", 0.0, 10.0)).collect();
+        let mut validation_samples = Vec::new();
+        let mut labels = Vec::new();
+        for _i in 0..50 {
+            validation_samples.push(random_sample("normal", 0.0, 10.0));
+            labels.push(false);
+        }
+        for _i in 0..10 {
+            validation_samples.push(random_sample("outlier", 20.0, 30.0));
+            labels.push(true);
+        }
+
+        let space = SearchSpace {
+            num_trees: vec![10, 50],
+            max_samples: vec![MaxSamples::Count(64), MaxSamples::Fraction(1.0)],
+            max_depth: vec![4, 8],
+        };
+        let criterion = LabeledAuc::new(&validation_samples, &labels);
+        let results = search(&training_samples, &space, Search::Grid, &criterion, Some(1)).unwrap();
+        println!("Best: {:?} AUC: {:.3}", results.best, results.best_score);
+        assert_eq!(results.trials.len(), 8);
+        assert!(results.trials.iter().all(|trial| trial.score.unwrap() <= results.best_score));
+        assert!(results.best_score > 0.9);
+
+        // Invalid combinations, such as a depth that 8 samples can't reach, are recorded as failed trials.
+        let partly_invalid_space = SearchSpace { num_trees: vec![10], max_samples: vec![MaxSamples::Count(8), MaxSamples::Count(256)], max_depth: vec![8] };
+        let results = search(&training_samples, &partly_invalid_space, Search::Grid, &criterion, Some(1)).unwrap();
+        assert_eq!(results.best.max_samples, MaxSamples::Count(256));
+        assert!(results.trials[0].score.is_none() && results.trials[0].error.is_some());
+        let invalid_space = SearchSpace { num_trees: vec![10], max_samples: vec![MaxSamples::Count(8)], max_depth: vec![8] };
+        assert!(search(&training_samples, &invalid_space, Search::Grid, &criterion, Some(1)).is_err());
+
+        // Random search tries distinct configurations, and accepts any criterion.
+        let criterion = |forest: &crate::isolation_forest::Forest| forest.num_trees() as f64;
+        let results = search(&training_samples, &space, Search::Random { num_configurations: 3 }, &criterion, None).unwrap();
+        assert_eq!(results.trials.len(), 3);
+        assert_eq!(results.best.num_trees, results.best_score as u32);
+    }
+
+    #[test]
+    fn metrics_test() {
+        use crate::metrics::{MassVolume, quality_curves};
+        use crate::tuning::Criterion;
+
+        // Training data clustered in one corner of a wider bounding box.
+        let random_sample = |min: f64, max: f64| {
+            make_sample("", &[("x", rand::rng().random_range(min..max)), ("y", rand::rng().random_range(min..max))])
+        };
+        let mut forest = crate::isolation_forest::Forest::new(50, 8);
+        for _i in 0..250 {
+            forest.add_sample(random_sample(0.0, 10.0));
+        }
+        for _i in 0..5 {
+            forest.add_sample(random_sample(0.0, 100.0));
+        }
+        forest.create();
+
+        let clustered: Vec<_> = (0..100).map(|_| random_sample(1.0, 9.0)).collect();
+        let spread: Vec<_> = (0..100).map(|_| random_sample(0.0, 100.0)).collect();
+        let clustered_curves = quality_curves(&forest, &clustered, 2000, Some(1)).unwrap();
+        let spread_curves = quality_curves(&forest, &spread, 2000, Some(1)).unwrap();
+        println!("EM: {:.3e} vs {:.3e}, MV: {:.1} vs {:.1}", clustered_curves.excess_mass.auc, spread_curves.excess_mass.auc,
+            clustered_curves.mass_volume.auc, spread_curves.mass_volume.auc);
+
+        assert_eq!(clustered_curves.excess_mass.y[0], 1.0);
+        assert!(clustered_curves.mass_volume.y.windows(2).all(|y| y[0] <= y[1]));
+
+        // Held-out data that matches what the forest learned needs less volume to hold the same mass.
+        assert!(clustered_curves.mass_volume.auc < spread_curves.mass_volume.auc);
+        assert!(clustered_curves.excess_mass.auc > spread_curves.excess_mass.auc);
+        let criterion = MassVolume { held_out_samples: &clustered, num_uniform_samples: 2000, seed: Some(1) };
+        assert_eq!(criterion.evaluate(&forest), -clustered_curves.mass_volume.auc);
+        assert!(quality_curves(&forest, &[], 2000, None).is_err());
+    }
+
+    #[test]
+    fn drift_test() {
+        use crate::drift::DriftMonitor;
+        use rand::SeedableRng;
+
+        // Seeded, so that the steady batch can't drift by chance.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(45);
+        let mut random_sample = |x_min: f64, y_min: f64| {
+            make_sample("", &[("x", rng.random_range(x_min..x_min + 10.0)), ("y", rng.random_range(y_min..y_min + 10.0))])
+        };
+        let mut forest = crate::isolation_forest::Forest::builder().num_trees(50).seed(45).build().unwrap();
+        for _i in 0..500 {
+            forest.add_sample(random_sample(0.0, 0.0));
+        }
+        forest.create();
+
+        let validation: Vec<_> = (0..500).map(|_| random_sample(0.0, 0.0)).collect();
+        let monitor = DriftMonitor::new(&forest).reference_samples(&validation).significance(0.001);
+        let steady: Vec<_> = (0..200).map(|_| random_sample(0.0, 0.0)).collect();
+        let report = monitor.check(&steady).unwrap();
+        assert_eq!(report.features.len(), 2);
+        assert!(!report.drifted(), "{:?}", report);
+
+        // Only y moves, which also pushes the scores up.
+        let shifted: Vec<_> = (0..200).map(|_| random_sample(0.0, 5.0)).collect();
+        let report = monitor.check(&shifted).unwrap();
+        println!("{}", serde_json::to_string(&report).unwrap());
+        assert_eq!(report.drifted_features(), vec!["y"]);
+        let scores = report.scores.unwrap();
+        assert!(scores.drifted);
+        assert!(scores.batch_mean > scores.reference_mean);
+        assert!(monitor.check(&[]).is_err());
+
+        // A feature that goes missing from the whole batch is reported, as is one that goes missing from most of it.
+        let without_y: Vec<_> = (0..200).map(|_| make_sample("", &[("x", random_sample(0.0, 0.0).feature_value("x").unwrap())])).collect();
+        let report = monitor.check(&without_y).unwrap();
+        assert_eq!(report.features.len(), 2);
+        assert_eq!(report.drifted_features(), vec!["y"]);
+        assert_eq!(report.features[1].missing_fraction, 1.0);
+        assert!(report.features[1].shift.is_none());
+        let mostly_without_y: Vec<_> = (0..200).map(|i| if i % 10 == 0 { random_sample(0.0, 0.0) } else { without_y[i].clone() }).collect();
+        let report = monitor.check(&mostly_without_y).unwrap();
+        assert_eq!(report.features[1].reference_missing_fraction, 0.0);
+        assert!((report.features[1].missing_fraction - 0.9).abs() < 1e-9);
+        assert_eq!(report.drifted_features(), vec!["y"]);
+    }
+
+    #[test]
+    fn calibration_test() {
+        use crate::calibration::Calibration;
+
+        // Averaging two uniform values makes the training data densest in the middle.
+        let random_value = |min: f64, max: f64| (rand::rng().random_range(min..max) + rand::rng().random_range(min..max)) / 2.0;
+        let random_sample = |min: f64, max: f64| make_sample("", &[("x", random_value(min, max)), ("y", random_value(min, max))]);
+        let mut forest = crate::isolation_forest::Forest::new(50, 10);
+        assert!(forest.calibrate_tail(0.1).is_err());
+        for _i in 0..500 {
+            forest.add_sample(random_sample(0.0, 10.0));
+        }
+        forest.create();
+        assert_eq!(forest.probability(&random_sample(0.0, 10.0)), None);
+
+        // Unsupervised: samples outside the tail of the training scores get zero probability, so most new samples from
+        // the training distribution do, and outliers go to the top of the tail.
+        forest.calibrate_tail(0.1).unwrap();
+        let inlier = make_sample("", &[("x", 5.0), ("y", 5.0)]);
+        let outlier = make_sample("", &[("x", 50.0), ("y", 50.0)]);
+        let inlier_probability = forest.probability(&inlier).unwrap();
+        let outlier_probability = forest.probability(&outlier).unwrap();
+        let probabilities: Vec<f64> = (0..200).map(|_| forest.probability(&random_sample(0.0, 10.0)).unwrap()).collect();
+        let num_in_tail = probabilities.iter().filter(|probability| **probability > 0.0).count();
+        let mean_probability = probabilities.iter().sum::<f64>() / probabilities.len() as f64;
+        println!("Tail: inlier {:.3} outlier {:.3} in tail {} mean {:.3}", inlier_probability, outlier_probability, num_in_tail, mean_probability);
+        assert_eq!(inlier_probability, 0.0);
+        assert!(outlier_probability > 0.99);
+        assert!(num_in_tail < 50);
+        assert!(mean_probability < 0.15);
+
+        // The calibration is saved with the forest.
+        let loaded = crate::isolation_forest::Forest::load(&forest.dump()).unwrap();
+        assert!(matches!(loaded.calibration(), Some(Calibration::Tail { .. })));
+        assert!((loaded.probability(&outlier).unwrap() - outlier_probability).abs() < 1e-9);
+
+        // Supervised, from a few labeled scores.
+        let mut scores = Vec::new();
+        let mut labels = Vec::new();
+        for _i in 0..40 {
+            scores.push(forest.normalized_score(&random_sample(0.0, 10.0)));
+            labels.push(false);
+        }
+        for _i in 0..10 {
+            scores.push(forest.normalized_score(&random_sample(20.0, 40.0)));
+            labels.push(true);
+        }
+        for calibration in [Calibration::platt(&scores, &labels).unwrap(), Calibration::isotonic(&scores, &labels).unwrap()] {
+            forest.set_calibration(calibration);
+            let inlier_probability = forest.probability(&inlier).unwrap();
+            let outlier_probability = forest.probability(&outlier).unwrap();
+            println!("{:?}: inlier {:.3} outlier {:.3}", forest.calibration().unwrap(), inlier_probability, outlier_probability);
+            assert!(inlier_probability < 0.05);
+            assert!(outlier_probability > 0.75);
+        }
+        assert!(Calibration::platt(&scores, &vec![false; scores.len()]).is_err());
+    }
+
+    #[test]
+    fn fit_from_iter_test() {
+        use crate::isolation_forest::{Forest, MaxSamples};
+
+        // The samples are generated lazily, and only the reservoir is kept.
+        let samples = (0..50000).map(|_| {
+            make_sample("normal", &[("x", rand::rng().random_range(0.0..10.0)), ("y", rand::rng().random_range(0.0..10.0))])
+        });
+        let mut forest = Forest::builder().num_trees(20).max_samples(MaxSamples::Count(64)).seed(7).build().unwrap();
+        assert_eq!(forest.fit_from_iter(samples), 50000);
+        assert_eq!(forest.num_trees(), 20);
+        assert_eq!(forest.training_values("x").unwrap().len(), 20 * 64);
+        assert!(forest.provenance().iter().all(|provenance| provenance.num_samples == 64));
+
+        let inlier = make_sample("inlier", &[("x", 5.0), ("y", 5.0)]);
+        let outlier = make_sample("outlier", &[("x", 50.0), ("y", 50.0)]);
+        assert!(forest.normalized_score(&outlier) > forest.normalized_score(&inlier));
+
+        // Short streams are kept whole. Values added one at a time are sorted once, when the trees are built.
+        let mut forest = Forest::new(10, 8);
+        assert_eq!(forest.fit_from_iter((0..100).map(|i| make_sample("", &[("x", i as f64)]))), 100);
+        assert_eq!(forest.training_values("x").unwrap().len(), 100);
+        let mut forest = Forest::new(10, 8);
+        for _i in 0..100 {
+            forest.add_sample(make_sample("", &[("x", rand::rng().random_range(0.0..10.0))]));
+        }
+        assert_eq!(forest.feature_names(), vec!["x"]);
+        forest.create();
+        for _i in 0..100 {
+            forest.add_sample(make_sample("", &[("x", rand::rng().random_range(0.0..10.0))]));
+        }
+        forest.grow(5);
+        let values = forest.training_values("x").unwrap();
+        assert_eq!(values.len(), 200);
+        assert!(values.windows(2).all(|values| values[0] <= values[1]));
+    }
+
+    #[test]
+    fn segmented_forest_test() {
+        use crate::isolation_forest::{Forest, MaxSamples};
+        use crate::segmented::SegmentedForest;
+
+        let options = Forest::builder().num_trees(50).max_samples(MaxSamples::Count(64)).seed(3);
+        let mut forest = SegmentedForest::new(options, 20).unwrap();
+        for _i in 0..200 {
+            forest.add_sample("web", make_sample("", &[("x", rand::rng().random_range(0.0..10.0))]));
+            forest.add_sample("db", make_sample("", &[("x", rand::rng().random_range(100.0..110.0))]));
+        }
+        for i in 0..1000 {
+            forest.add_sample(&format!("host{}", i), make_sample("", &[("x", rand::rng().random_range(0.0..110.0))]));
+        }
+        forest.create();
+        assert_eq!(forest.num_segments(), 1002);
+
+        // A value that is normal for one segment is anomalous for another.
+        let sample = make_sample("", &[("x", 105.0)]);
+        assert!(forest.normalized_score("db", &sample) < forest.normalized_score("web", &sample));
+        assert!(!forest.uses_global("web"));
+        assert!(forest.uses_global("host1"));
+        assert!(forest.uses_global("unknown"));
+        assert_eq!(forest.score("unknown", &sample), forest.global_forest().score(&sample));
+
+        // Every segment is saved to, and read back from, one file.
+        let mut file = Vec::new();
+        forest.write(&mut file).unwrap();
+        let mut loaded = SegmentedForest::read(file.as_slice()).unwrap();
+        assert_eq!(loaded.num_segments(), 1002);
+        for segment_name in ["web", "db", "host1", "unknown"] {
+            assert_eq!(loaded.score(segment_name, &sample), forest.score(segment_name, &sample));
+        }
+
+        // A loaded segment keeps its trees when trained further, replacing only a share of them.
+        for _i in 0..20 {
+            loaded.add_sample("web", make_sample("", &[("x", rand::rng().random_range(0.0..10.0))]));
+        }
+        loaded.create();
+        assert_eq!(loaded.forest("web").num_trees(), 50);
+        let center = make_sample("", &[("x", 5.0)]);
+        println!("Web after loading: center {:.2} outlier {:.2}", loaded.normalized_score("web", &center), loaded.normalized_score("web", &sample));
+        assert!(loaded.normalized_score("web", &center) < 0.5);
+        assert!(loaded.normalized_score("web", &sample) > 0.8);
+
+        // Invalid options in a saved file are rejected when it is loaded.
+        assert!(SegmentedForest::load(&forest.dump()).is_ok());
+        assert!(SegmentedForest::load(&forest.dump().replace("\"num_trees\":50", "\"num_trees\":0")).is_err());
+        assert!(SegmentedForest::new(Forest::builder().num_trees(0), 20).is_err());
+    }
+
+    #[test]
+    fn semi_supervised_test() {
+        use crate::isolation_forest::{Forest, MaxSamples, Sample};
+        use crate::tuning::roc_auc;
+
+        // Versicolor is normal and virginica, which overlaps it, is anomalous. A few of the virginica samples mixed
+        // into the training data are known anomalies.
+        let mut reader = csv::Reader::from_path("../data/iris.data.txt").unwrap();
+        let mut versicolor = Vec::new();
+        let mut virginica = Vec::new();
+        for record in reader.records() {
+            let record = record.unwrap();
+            let mut features: Vec<_> = ["sepal length in cm", "sepal width in cm", "petal length in cm", "petal width in cm"].iter().enumerate()
+                .map(|(i, name)| crate::isolation_forest::Feature::new(name, record[i].parse::<f64>().unwrap()))
+                .collect();
+            let mut sample = Sample::new(&record[4]);
+            sample.add_features(&mut features);
+            match &record[4] {
+                "Iris-versicolor" => versicolor.push(sample),
+                "Iris-virginica" => virginica.push(sample),
+                _ => {}
+            }
+        }
+
+        let mut total_unsupervised_auc = 0.0;
+        let mut total_semi_supervised_auc = 0.0;
+        for seed in 0..10 {
+            let train = |labeled: bool| {
+                let mut forest = Forest::builder().num_trees(100).max_samples(MaxSamples::Count(64)).seed(seed).build().unwrap();
+                for sample in versicolor.iter().step_by(2) {
+                    forest.add_sample(sample.clone());
+                }
+                for (i, sample) in virginica.iter().step_by(5).enumerate() {
+                    let mut sample = sample.clone();
+                    if labeled && i < 5 {
+                        sample.set_label(true);
+                    }
+                    forest.add_sample(sample);
+                }
+                forest.create();
+                forest
+            };
+
+            let test_samples: Vec<&Sample> = versicolor.iter().skip(1).step_by(2).chain(virginica.iter().skip(1).step_by(5)).collect();
+            let labels: Vec<bool> = test_samples.iter().map(|sample| sample.name == "Iris-virginica").collect();
+            let auc = |forest: &Forest| roc_auc(&test_samples.iter().map(|sample| forest.score(sample)).map(|score| -score).collect::<Vec<_>>(), &labels);
+            let unsupervised = train(false);
+            let semi_supervised = train(true);
+            assert!(unsupervised.tree_weights().iter().all(|weight| *weight == 1.0));
+            let uncertainty = semi_supervised.score_with_uncertainty(test_samples[0], 0.95);
+            assert_eq!(uncertainty.mean, semi_supervised.normalized_score(test_samples[0]));
+            total_unsupervised_auc += auc(&unsupervised);
+            total_semi_supervised_auc += auc(&semi_supervised);
+
+            // Growing a labeled forest weights the new trees and keeps the feedback given to the older ones.
+            let mut semi_supervised = semi_supervised;
+            semi_supervised.add_feedback(test_samples[0], false);
+            let weights = semi_supervised.tree_weights();
+            semi_supervised.grow(5);
+            let grown_weights = semi_supervised.tree_weights();
+            assert_eq!(grown_weights.len(), weights.len() + 5);
+            let scale = grown_weights[0] / weights[0];
+            assert!(weights.iter().zip(&grown_weights).all(|(weight, grown_weight)| (grown_weight / weight - scale).abs() < 1e-9));
+
+            // The weights still average 1 after trees are pruned, grown or merged.
+            let mean_weight = |forest: &Forest| forest.tree_weights().iter().sum::<f64>() / forest.num_trees() as f64;
+            semi_supervised.prune_oldest(10);
+            assert!((mean_weight(&semi_supervised) - 1.0).abs() < 1e-9);
+            semi_supervised.grow(5);
+            assert!((mean_weight(&semi_supervised) - 1.0).abs() < 1e-9);
+            semi_supervised.merge(unsupervised).unwrap();
+            assert!((mean_weight(&semi_supervised) - 1.0).abs() < 1e-9);
+        }
+        println!("Mean AUC: unsupervised {:.3}, semi-supervised {:.3}", total_unsupervised_auc / 10.0, total_semi_supervised_auc / 10.0);
+        assert!(total_semi_supervised_auc > total_unsupervised_auc);
+    }
+
+    #[test]
+    fn active_learning_test() {
+        use crate::active_learning::{QueryStrategy, query};
+        use rand::SeedableRng;
+
+        // A rare but benign cluster and the true anomalies, which are only unusual in x, both stand out from the normal data.
+        // Seeded, as a handful of labels can't always outweigh an unlucky forest.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(50);
+        let mut random_sample = |name: &str, x: f64, y: f64, spread: f64| {
+            make_sample(name, &[("x", x + rng.random_range(0.0..spread)), ("y", y + rng.random_range(0.0..spread))])
+        };
+        let mut forest = crate::isolation_forest::Forest::new(100, 10);
+        forest.set_seed(50);
+        for _i in 0..500 {
+            forest.add_sample(random_sample("normal", 0.0, 0.0, 10.0));
+        }
+        for _i in 0..10 {
+            forest.add_sample(random_sample("benign", 30.0, 30.0, 1.0));
+        }
+        forest.create();
+
+        let mut candidates = Vec::new();
+        for _i in 0..50 {
+            candidates.push(random_sample("normal", 0.0, 0.0, 10.0));
+            candidates.push(random_sample("benign", 30.0, 30.0, 1.0));
+        }
+        for _i in 0..10 {
+            candidates.push(random_sample("anomaly", 30.0, 0.0, 1.0));
+        }
+        let anomaly = make_sample("anomaly", &[("x", 30.5), ("y", 0.5)]);
+        let benign = make_sample("benign", &[("x", 30.5), ("y", 30.5)]);
+        let gap_before = forest.normalized_score(&anomaly) - forest.normalized_score(&benign);
+
+        // Each round, the analyst labels the most anomalous candidates, which are removed from the pool.
+        for _round in 0..5 {
+            let mut queries = query(&forest, &candidates, 5, QueryStrategy::MostAnomalous);
+            assert_eq!(queries.len(), 5);
+            queries.sort_unstable();
+            for index in queries.into_iter().rev() {
+                let sample = candidates.remove(index);
+                forest.add_feedback(&sample, sample.name == "anomaly");
+            }
+        }
+        let gap_after = forest.normalized_score(&anomaly) - forest.normalized_score(&benign);
+        println!("Anomaly minus benign score: before {:.3}, after {:.3}", gap_before, gap_after);
+        assert!(gap_after > gap_before);
+        assert!(gap_after > 0.0);
+        let weights = forest.tree_weights();
+        assert!((weights.iter().sum::<f64>() / weights.len() as f64 - 1.0).abs() < 1e-9);
+
+        // The uncertainty uses the tree weights, so its mean stays the normalized score.
+        let uncertainty = forest.score_with_uncertainty(&anomaly, 0.95);
+        assert_eq!(uncertainty.mean, forest.normalized_score(&anomaly));
+        assert!(uncertainty.lower < uncertainty.mean && uncertainty.mean < uncertainty.upper);
+
+        // The other strategies rank every candidate.
+        assert_eq!(query(&forest, &candidates, 1000, QueryStrategy::NearThreshold).len(), candidates.len());
+        assert_eq!(query(&forest, &candidates, 3, QueryStrategy::MostUncertain).len(), 3);
+    }
+
+    #[test]
+    fn load_test() {
+        let mut forest = crate::isolation_forest::Forest::new(10, 10);
+
+        for _i in 0..50 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+            forest.add_sample(make_sample("training", &[("x", x), ("y", y)]));
+        }
+        forest.create();
+
+        let loaded_forest = crate::isolation_forest::Forest::load(&forest.dump()).unwrap();
+        let sample = make_sample("test", &[("x", 10.0), ("y", 30.0)]);
+        assert_eq!(loaded_forest.num_trees(), forest.num_trees());
+        assert_eq!(loaded_forest.score(&sample), forest.score(&sample));
+        assert!(crate::isolation_forest::Forest::load("{}").is_err());
+    }
+
+    #[test]
+    fn json_lines_test() {
+        let mut forest = crate::isolation_forest::Forest::new(10, 10);
+
+        for _i in 0..50 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+            forest.add_sample(make_sample("training", &[("x", x), ("y", y)]));
+        }
+        forest.create();
+
+        let input = concat!(
+            "{\"name\": \"a\", \"features\": [{\"name\": \"x\", \"value\": 10.0}, {\"name\": \"y\", \"value\": 30.0}], \"host\": \"web1\"}\n",
+            "\n",
+            "{\"features\": [{\"name\": \"x\", \"value\": 100.0}]}\n",
+        );
+        let mut output = Vec::new();
+        let num_scored = crate::jsonl::score_json_lines(&forest, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(num_scored, 2);
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["host"], "web1");
+        let score = forest.score(&make_sample("a", &[("x", 10.0), ("y", 30.0)]));
+        assert!((lines[0]["score"].as_f64().unwrap() - score).abs() < 1e-9);
+        assert!(lines[1]["normalized_score"].is_f64());
+
+        match crate::jsonl::score_json_lines(&forest, "{\"features\": 1}\n".as_bytes(), &mut Vec::new()) {
+            Err(crate::isolation_forest::ForestError::ParseError(message)) => assert!(message.starts_with("Line 1")),
+            _ => panic!("Expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn timeseries_test() {
+        let config = crate::timeseries::WindowConfig::default();
+        let mut forest = crate::isolation_forest::Forest::new(100, 10);
+
+        // A noisy sine wave sampled at irregular intervals, with a spike and a gap.
+        let mut series = Vec::new();
+        let mut timestamp = 0.0;
+        for i in 0..500 {
+            timestamp += rand::rng().random_range(0.8..1.2);
+            if i == 300 {
+                timestamp += 50.0;
+            }
+            let value = (timestamp / 10.0_f64).sin() + rand::rng().random_range(-0.1..0.1);
+            series.push(crate::timeseries::Point::new(timestamp, value));
+        }
+        series[200].value = 5.0;
+
+        let scores = crate::timeseries::fit_score(&mut forest, &series, &config);
+        assert_eq!(scores.len(), series.len());
+
+        // No scores until a full window is available, at the start and after the gap.
+        assert!(scores[..config.window_size - 1].iter().all(|score| score.is_none()));
+        assert!(scores[300..300 + config.window_size - 1].iter().all(|score| score.is_none()));
+        assert!(scores[300 + config.window_size - 1].is_some());
+
+        // The spike scores higher than almost every other point.
+        let spike_score = scores[200].unwrap();
+        let num_scored = scores.iter().flatten().count();
+        let num_higher = scores.iter().flatten().filter(|score| **score > spike_score).count();
+        assert!(num_higher < num_scored / 10);
+    }
+
+    #[test]
+    fn half_space_trees_test() {
+        use crate::half_space_trees::HalfSpaceTrees;
+
+        let mut detector = HalfSpaceTrees::new(25, 10, 250, &[("x", 0.0, 25.0), ("y", 15.0, 45.0)]).unwrap();
+
+        // Stream samples similar to the training samples used by the other tests.
+        for _i in 0..1000 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+            detector.update(&make_sample("normal", &[("x", x), ("y", y)]));
+        }
+        assert!(detector.is_ready());
+
+        let mut avg_control_normalized_score = 0.0;
+        for _i in 0..100 {
+            let x = rand::rng().random_range(0..25) as f64;
+            let y = rand::rng().random_range(15..45) as f64;
+            avg_control_normalized_score += detector.normalized_score(&make_sample("normal", &[("x", x), ("y", y)])) / 100.0;
+        }
+        let outlier_normalized_score = detector.normalized_score(&make_sample("outlier", &[("x", 60.0), ("y", 90.0)]));
+        println!("Control: {:.2} Outlier: {:.2}", avg_control_normalized_score, outlier_normalized_score);
+
+        assert!(avg_control_normalized_score < 0.5);
+        assert!(outlier_normalized_score > 0.5);
+
+        // Invalid settings are errors rather than panics.
+        assert!(HalfSpaceTrees::from_samples(25, 10, 250, &[]).is_err());
+        assert!(HalfSpaceTrees::new(25, 10, 250, &[("x", 25.0, 0.0)]).is_err());
+        assert!(HalfSpaceTrees::new(25, 10, 250, &[("x", f64::NAN, 25.0)]).is_err());
+        assert!(HalfSpaceTrees::new(25, 63, 250, &[("x", 0.0, 25.0)]).is_err());
+        assert!(HalfSpaceTrees::new(1, 21, 250, &[("x", 0.0, 25.0)]).is_err());
+        assert!(HalfSpaceTrees::new(25, 20, 250, &[("x", 0.0, 25.0)]).is_err());
+        assert!(HalfSpaceTrees::new(1, 20, 250, &[("x", 0.0, 25.0)]).is_ok());
+        assert!(HalfSpaceTrees::new(25, 10, 0, &[("x", 0.0, 25.0)]).is_err());
+
+        // Seeded trees are the same.
+        let build = || {
+            let mut detector = HalfSpaceTrees::builder().num_trees(10).max_depth(8).window_size(50).seed(36).build(&[("x", 0.0, 25.0)]).unwrap();
+            for x in 0..50 {
+                detector.update(&make_sample("normal", &[("x", x as f64 / 2.0)]));
+            }
+            detector.score(&make_sample("test", &[("x", 7.0)]))
+        };
+        assert_eq!(build(), build());
+    }
+
+    #[test]
+    fn missing_value_policy_test() {
+        let mut forest = crate::isolation_forest::Forest::new(10, 10);
+
+        for x in 0..100 {
+            forest.add_sample(make_sample("training", &[("x", x as f64), ("y", 50.0)]));
+        }
+        forest.create();
+
+        // The median of y is 50, so imputing it gives the same score as providing it.
+        let missing_sample = make_sample("missing", &[("x", 10.0)]);
+        let complete_sample = make_sample("complete", &[("x", 10.0), ("y", 50.0)]);
+        forest.set_missing_value_policy(crate::isolation_forest::MissingValuePolicy::ImputeMedian);
+        assert_eq!(forest.score(&missing_sample), forest.score(&complete_sample));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn array_test() {
+        let mut forest = crate::isolation_forest::Forest::new(50, 10);
+        let mut data = ndarray::Array2::<f64>::zeros((200, 2));
+
+        for mut row in data.rows_mut() {
+            row[0] = rand::rng().random_range(0.0..25.0);
+            row[1] = rand::rng().random_range(15.0..45.0);
+        }
+
+        assert!(forest.fit_array(data.view(), &["x"]).is_err());
+        forest.fit_array(data.view(), &["x", "y"]).unwrap();
+        assert_eq!(forest.num_trees(), 50);
+
+        let test_data = ndarray::arr2(&[[10.0, 30.0], [100.0, 200.0], [10.0, f64::NAN]]);
+        let scores = forest.score_array(test_data.view()).unwrap();
+        let normalized_scores = forest.normalized_score_array(test_data.view()).unwrap();
+        assert!(forest.score_array(ndarray::arr2(&[[10.0]]).view()).is_err());
+
+        // Scoring a matrix row is the same as scoring the equivalent sample, and NaN is treated as missing.
+        assert_eq!(scores[0], forest.score(&make_sample("normal", &[("x", 10.0), ("y", 30.0)])));
+        assert_eq!(scores[2], forest.score(&make_sample("missing", &[("x", 10.0)])));
+        assert!(normalized_scores[0] < normalized_scores[1]);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn record_batch_test() {
+        use arrow::array::{Float64Array, Int32Array, StringArray};
+        use arrow::datatypes::{DataType, Field, Schema};
+        use arrow::record_batch::RecordBatch;
+        use std::sync::Arc;
+
+        let mut forest = crate::isolation_forest::Forest::new(50, 10);
+        let x: Vec<f64> = (0..200).map(|_| rand::rng().random_range(0.0..25.0)).collect();
+        let y: Vec<i32> = (0..200).map(|_| rand::rng().random_range(15..45)).collect();
+        let names: Vec<&str> = (0..200).map(|_| "training").collect();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, true),
+            Field::new("y", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![
+            Arc::new(Float64Array::from(x)),
+            Arc::new(Int32Array::from(y)),
+            Arc::new(StringArray::from(names)),
+        ]).unwrap();
+
+        match forest.fit_record_batches(std::slice::from_ref(&batch), &[]) {
+            Err(crate::isolation_forest::ForestError::UnsupportedColumnType { column, .. }) => assert_eq!(column, "name"),
+            _ => panic!("Expected an unsupported column type"),
+        }
+        assert_eq!(forest.fit_record_batches(std::slice::from_ref(&batch), &["x", "z"]), Err(crate::isolation_forest::ForestError::MissingColumn("z".to_string())));
+
+        // Round trip through a Parquet file.
+        let file_path = std::env::temp_dir().join(format!("isolation_forest_test_{}.parquet", std::process::id()));
+        let file = std::fs::File::create(&file_path).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, schema.clone(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        forest.fit_parquet(&file_path, &["x", "y"]).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(forest.num_trees(), 50);
+
+        let test_schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, true),
+            Field::new("y", DataType::Int32, false),
+        ]));
+        let test_batch = RecordBatch::try_new(test_schema, vec![
+            Arc::new(Float64Array::from(vec![Some(10.0), Some(100.0), None])),
+            Arc::new(Int32Array::from(vec![30, 200, 30])),
+        ]).unwrap();
+        let scores = forest.score_record_batch(&test_batch).unwrap();
+        let normalized_scores = forest.normalized_score_record_batch(&test_batch).unwrap();
+
+        // Scoring a row is the same as scoring the equivalent sample, and nulls are treated as missing.
+        assert_eq!(scores.value(0), forest.score(&make_sample("normal", &[("x", 10.0), ("y", 30.0)])));
+        assert_eq!(scores.value(2), forest.score(&make_sample("missing", &[("y", 30.0)])));
+        assert!(normalized_scores.value(0) < normalized_scores.value(1));
+    }
+
+    #[cfg(feature = "polars")]
+    #[test]
+    fn dataframe_test() {
+        use polars::prelude::{DataFrame, NamedFrom, Series};
+
+        let mut forest = crate::isolation_forest::Forest::new(50, 10);
+        let x: Vec<f64> = (0..200).map(|_| rand::rng().random_range(0.0..25.0)).collect();
+        let y: Vec<i32> = (0..200).map(|_| rand::rng().random_range(15..45)).collect();
+        let names: Vec<&str> = (0..200).map(|_| "training").collect();
+        let df = DataFrame::new(vec![
+            Series::new("x".into(), x).into(),
+            Series::new("y".into(), y).into(),
+            Series::new("name".into(), names).into(),
+        ]).unwrap();
+
+        match forest.fit_dataframe(&df, &[]) {
+            Err(crate::isolation_forest::ForestError::UnsupportedColumnType { column, .. }) => assert_eq!(column, "name"),
+            _ => panic!("Expected an unsupported column type"),
+        }
+        forest.fit_dataframe(&df, &["x", "y"]).unwrap();
+        assert_eq!(forest.num_trees(), 50);
+
+        let test_df = DataFrame::new(vec![
+            Series::new("x".into(), vec![Some(10.0), Some(100.0), None]).into(),
+            Series::new("y".into(), vec![30, 200, 30]).into(),
+        ]).unwrap();
+
+        // Nulls are handled by the forest's missing value policy.
+        forest.set_missing_value_policy(crate::isolation_forest::MissingValuePolicy::ImputeMedian);
+        let scores = forest.score_dataframe(&test_df).unwrap();
+        let scores = scores.f64().unwrap();
+        assert_eq!(scores.get(0).unwrap(), forest.normalized_score(&make_sample("normal", &[("x", 10.0), ("y", 30.0)])));
+        assert_eq!(scores.get(2).unwrap(), forest.normalized_score(&make_sample("missing", &[("y", 30.0)])));
+        assert!(scores.get(0).unwrap() < scores.get(1).unwrap());
     }
 
     #[test]
@@ -133,6 +1236,7 @@ mod tests {
 
         let mut reader = csv::Reader::from_reader(file);
         let mut forest = crate::isolation_forest::Forest::new(10, 10);
+        forest.set_seed(26);
         let training_class_name = "Iris-setosa";
         let mut training_samples = Vec::new();
         let mut test_samples = Vec::new();
@@ -143,6 +1247,9 @@ mod tests {
         let mut num_control_tests = 0;
         let mut num_outlier_tests = 0;
 
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(26);
+
         for record in reader.records() {
             let record = record.unwrap();
 
@@ -152,17 +1259,18 @@ mod tests {
             let petal_width_cm: f64 = record[3].parse().unwrap();
             let name: String = record[4].parse().unwrap();
 
-            let mut features = crate::isolation_forest::FeatureList::new();
-            features.push(crate::isolation_forest::Feature::new("sepal length in cm", (sepal_length_cm * 10.0) as u64));
-            features.push(crate::isolation_forest::Feature::new("sepal width in cm", (sepal_width_cm * 10.0) as u64));
-            features.push(crate::isolation_forest::Feature::new("petal length in cm", (petal_length_cm * 10.0) as u64));
-            features.push(crate::isolation_forest::Feature::new("petal width in cm", (petal_width_cm * 10.0) as u64));
+            let mut features = vec![
+                crate::isolation_forest::Feature::new("sepal length in cm", sepal_length_cm),
+                crate::isolation_forest::Feature::new("sepal width in cm", sepal_width_cm),
+                crate::isolation_forest::Feature::new("petal length in cm", petal_length_cm),
+                crate::isolation_forest::Feature::new("petal width in cm", petal_width_cm),
+            ];
 
             let mut sample = crate::isolation_forest::Sample::new(&name);
             sample.add_features(&mut features);
 
-            // Randomly split the samples into training and test samples.
-            let x = rand::rng().random_range(0..10) as u64;
+            // Randomly split the samples into training and test samples. Seeded, so the split is repeatable.
+            let x = rng.random_range(0..10) as u64;
             if x > 5 && name == training_class_name {
                 forest.add_sample(sample.clone());
                 training_samples.push(sample);
@@ -0,0 +1,181 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Unsupervised measures of a forest's quality, which need no labels: the Excess-Mass and Mass-Volume curves of
+//! Goix (2016), computed from the forest's scores on held-out samples and on samples drawn uniformly from the
+//! bounding box of the training data.
+
+extern crate rand;
+extern crate serde;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use self::serde::Serialize;
+use crate::isolation_forest::{Forest, ForestError, Sample};
+use crate::tuning::Criterion;
+
+const ALPHA_MIN: f64 = 0.9; // Smallest mass on the Mass-Volume curve
+const ALPHA_MAX: f64 = 0.999; // Largest mass on the Mass-Volume curve
+const NUM_ALPHAS: usize = 1000; // Number of points on the Mass-Volume curve
+const EM_LIMIT: f64 = 0.9; // The Excess-Mass curve is integrated until it drops to this value
+const NUM_LEVELS: usize = 10000; // Number of points on the Excess-Mass curve
+
+/// A curve, along with the area under it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Curve {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub auc: f64,
+}
+
+impl Curve {
+    fn new(x: Vec<f64>, y: Vec<f64>) -> Curve {
+        let auc = x.windows(2).zip(y.windows(2)).map(|(x, y)| (x[1] - x[0]) * (y[0] + y[1]) / 2.0).sum();
+        Curve { x, y, auc }
+    }
+}
+
+/// The Excess-Mass and Mass-Volume curves of a forest. Higher areas under the Excess-Mass curve and lower areas
+/// under the Mass-Volume curve are better.
+#[derive(Clone, Debug, Serialize)]
+pub struct QualityCurves {
+    pub excess_mass: Curve,
+    pub mass_volume: Curve,
+}
+
+/// How normal each sample looks to the forest, where higher values are more normal.
+fn normality(forest: &Forest, feature_value: &dyn Fn(&str) -> Option<f64>) -> f64 {
+    -forest.normalized_score_with(feature_value)
+}
+
+/// Computes the Excess-Mass and Mass-Volume curves of the forest from its scores on the held-out samples and on the
+/// given number of samples drawn uniformly from the bounding box of the training data. Giving a seed makes the
+/// uniform samples reproducible.
+pub fn quality_curves(forest: &Forest, held_out_samples: &[Sample], num_uniform_samples: usize, seed: Option<u64>) -> Result<QualityCurves, ForestError> {
+    if held_out_samples.is_empty() || num_uniform_samples == 0 {
+        return Err(ForestError::InvalidParameter("quality curves need held-out and uniform samples".to_string()));
+    }
+
+    // Features without any spread don't add to the volume of the bounding box.
+    let ranges: Vec<(String, f64, f64)> = forest.feature_names().into_iter()
+        .filter_map(|feature_name| forest.feature_range(&feature_name).map(|(min, max)| (feature_name, min, max)))
+        .collect();
+    let volume: f64 = ranges.iter().map(|(_, min, max)| max - min).filter(|width| *width > 0.0).product();
+    if ranges.is_empty() {
+        return Err(ForestError::InvalidParameter("the forest has no training data".to_string()));
+    }
+
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
+    let mut uniform_scores: Vec<f64> = (0..num_uniform_samples).map(|_| {
+        let values: Vec<f64> = ranges.iter().map(|(_, min, max)| if min < max { rng.random_range(*min..*max) } else { *min }).collect();
+        normality(forest, &|feature_name: &str| ranges.iter().position(|(name, ..)| name == feature_name).map(|i| values[i]))
+    }).collect();
+    let mut scores: Vec<f64> = held_out_samples.iter()
+        .map(|sample| normality(forest, &|feature_name: &str| sample.feature_value(feature_name)))
+        .collect();
+    scores.sort_unstable_by(f64::total_cmp);
+    uniform_scores.sort_unstable_by(f64::total_cmp);
+
+    Ok(QualityCurves {
+        excess_mass: excess_mass(&scores, &uniform_scores, volume),
+        mass_volume: mass_volume(&scores, &uniform_scores, volume),
+    })
+}
+
+/// Returns the fraction of the sorted scores that are above (or, if inclusive, at or above) the level.
+fn fraction_above(sorted_scores: &[f64], level: f64, inclusive: bool) -> f64 {
+    let num_below = sorted_scores.partition_point(|score| if inclusive { *score < level } else { *score <= level });
+    (sorted_scores.len() - num_below) as f64 / sorted_scores.len() as f64
+}
+
+/// The Excess-Mass curve: for each level t, the largest difference between the mass of held-out samples above a
+/// score threshold and t times the volume above the same threshold. It is integrated until it drops to 0.9.
+fn excess_mass(scores: &[f64], uniform_scores: &[f64], volume: f64) -> Curve {
+    let mut thresholds = scores.to_vec();
+    thresholds.dedup();
+    let masses_and_volumes: Vec<(f64, f64)> = thresholds.iter()
+        .map(|&u| (fraction_above(scores, u, false), fraction_above(uniform_scores, u, false) * volume))
+        .collect();
+
+    let step = 0.01 / volume;
+    let mut x = Vec::new();
+    let mut y = Vec::new();
+    for i in 0..NUM_LEVELS {
+        let t = i as f64 * step;
+        let em = if i == 0 { 1.0 } else { masses_and_volumes.iter().map(|(mass, volume)| mass - t * volume).fold(0.0, f64::max) };
+        x.push(t);
+        y.push(em);
+        if em <= EM_LIMIT {
+            break;
+        }
+    }
+    Curve::new(x, y)
+}
+
+/// The Mass-Volume curve: for each mass between 0.9 and 0.999, the volume above the highest score threshold that
+/// has at least that fraction of the held-out samples above it.
+fn mass_volume(scores: &[f64], uniform_scores: &[f64], volume: f64) -> Curve {
+    let n = scores.len();
+    let mut x = Vec::with_capacity(NUM_ALPHAS);
+    let mut y = Vec::with_capacity(NUM_ALPHAS);
+    for i in 0..NUM_ALPHAS {
+        let alpha = ALPHA_MIN + (ALPHA_MAX - ALPHA_MIN) * i as f64 / (NUM_ALPHAS - 1) as f64;
+        let num_above = ((alpha * n as f64).ceil() as usize).clamp(1, n);
+        let threshold = scores[n - num_above];
+        x.push(alpha);
+        y.push(fraction_above(uniform_scores, threshold, true) * volume);
+    }
+    Curve::new(x, y)
+}
+
+/// Judges a forest by the area under its Excess-Mass curve on held-out samples, for use with `tuning::search`.
+pub struct ExcessMass<'a> {
+    pub held_out_samples: &'a [Sample],
+    pub num_uniform_samples: usize,
+    pub seed: Option<u64>,
+}
+
+impl Criterion for ExcessMass<'_> {
+    fn evaluate(&self, forest: &Forest) -> f64 {
+        quality_curves(forest, self.held_out_samples, self.num_uniform_samples, self.seed)
+            .map_or(f64::NEG_INFINITY, |curves| curves.excess_mass.auc)
+    }
+}
+
+/// Judges a forest by the area under its Mass-Volume curve on held-out samples, for use with `tuning::search`.
+/// Smaller areas are better, so the area is negated.
+pub struct MassVolume<'a> {
+    pub held_out_samples: &'a [Sample],
+    pub num_uniform_samples: usize,
+    pub seed: Option<u64>,
+}
+
+impl Criterion for MassVolume<'_> {
+    fn evaluate(&self, forest: &Forest) -> f64 {
+        quality_curves(forest, self.held_out_samples, self.num_uniform_samples, self.seed)
+            .map_or(f64::NEG_INFINITY, |curves| -curves.mass_volume.auc)
+    }
+}
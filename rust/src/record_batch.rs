@@ -0,0 +1,132 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Training and scoring `Forest`s from Apache Arrow record batches, and reading them from Parquet files.
+
+extern crate arrow;
+extern crate parquet;
+
+use self::arrow::array::{Array, ArrayRef, AsArray, Float64Array};
+use self::arrow::compute::cast;
+use self::arrow::datatypes::{DataType, Float64Type};
+use self::arrow::record_batch::RecordBatch;
+use self::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::isolation_forest::{Float64Vec, Forest, ForestError};
+
+/// Reads every record batch from a Parquet file.
+pub fn read_parquet<P: AsRef<Path>>(path: P) -> Result<Vec<RecordBatch>, ForestError> {
+    let file = std::fs::File::open(path).map_err(|e| ForestError::ReadError(e.to_string()))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|builder| builder.build())
+        .map_err(|e| ForestError::ReadError(e.to_string()))?;
+    reader.collect::<Result<Vec<_>, _>>().map_err(|e| ForestError::ReadError(e.to_string()))
+}
+
+/// Converts a column to f64 values. Numeric columns are cast, booleans are encoded as 0 and 1, and dates, times,
+/// and timestamps are encoded as their underlying integer values. Other types are rejected.
+fn column_to_f64(column_name: &str, column: &ArrayRef) -> Result<Float64Array, ForestError> {
+    let data_type = column.data_type();
+    let unsupported = |_| ForestError::UnsupportedColumnType { column: column_name.to_string(), data_type: data_type.to_string() };
+
+    let column = if data_type.is_numeric() || *data_type == DataType::Boolean {
+        cast(column, &DataType::Float64).map_err(unsupported)?
+    }
+    else if data_type.is_temporal() {
+        let column = cast(column, &DataType::Int64).map_err(unsupported)?;
+        cast(&column, &DataType::Float64).map_err(unsupported)?
+    }
+    else {
+        return Err(ForestError::UnsupportedColumnType { column: column_name.to_string(), data_type: data_type.to_string() });
+    };
+    Ok(column.as_primitive::<Float64Type>().clone())
+}
+
+impl Forest {
+    /// Trains the forest on record batches, replacing any existing training data and trees. Each named column
+    /// becomes a feature of the same name; if no column names are given, every column is used. Nulls are treated
+    /// as missing values.
+    pub fn fit_record_batches(&mut self, batches: &[RecordBatch], column_names: &[&str]) -> Result<(), ForestError> {
+        let column_names: Vec<String> = match (column_names.is_empty(), batches.first()) {
+            (true, Some(batch)) => batch.schema().fields().iter().map(|field| field.name().clone()).collect(),
+            _ => column_names.iter().map(|column_name| column_name.to_string()).collect(),
+        };
+
+        let mut columns = HashMap::new();
+        for batch in batches {
+            for column_name in &column_names {
+                let column = batch.column_by_name(column_name).ok_or_else(|| ForestError::MissingColumn(column_name.clone()))?;
+                let values = column_to_f64(column_name, column)?;
+                columns.entry(column_name.clone()).or_insert_with(Float64Vec::new).extend(values.iter().map(|value| value.unwrap_or(f64::NAN)));
+            }
+        }
+
+        self.set_columns(columns, column_names);
+        self.create();
+        Ok(())
+    }
+
+    /// Reads a Parquet file and trains the forest on its record batches. See `fit_record_batches`.
+    pub fn fit_parquet<P: AsRef<Path>>(&mut self, path: P, column_names: &[&str]) -> Result<(), ForestError> {
+        let batches = read_parquet(path)?;
+        self.fit_record_batches(&batches, column_names)
+    }
+
+    /// Scores each row of the record batch against the entire forest of trees. Results are the average path lengths.
+    /// Features without a column in the batch, and nulls, are treated as missing values.
+    pub fn score_record_batch(&self, batch: &RecordBatch) -> Result<Float64Array, ForestError> {
+        self.map_record_batch_rows(batch, |feature_value| self.score_with(feature_value))
+    }
+
+    /// Scores each row of the record batch against the entire forest of trees. Results are normalized so that values
+    /// close to 1 indicate anomalies and values close to zero indicate normal values. Features without a column in the
+    /// batch, and nulls, are treated as missing values.
+    pub fn normalized_score_record_batch(&self, batch: &RecordBatch) -> Result<Float64Array, ForestError> {
+        self.map_record_batch_rows(batch, |feature_value| self.normalized_score_with(feature_value))
+    }
+
+    fn map_record_batch_rows<S>(&self, batch: &RecordBatch, score: S) -> Result<Float64Array, ForestError>
+    where
+        S: Fn(&dyn Fn(&str) -> Option<f64>) -> f64,
+    {
+        let mut columns = Vec::new();
+        for column_name in self.column_names() {
+            if let Some(column) = batch.column_by_name(&column_name) {
+                let values = column_to_f64(&column_name, column)?;
+                columns.push((column_name, values));
+            }
+        }
+
+        let scores = (0..batch.num_rows()).map(|row| {
+            score(&|feature_name: &str| {
+                columns.iter()
+                    .find(|(column_name, _)| column_name == feature_name)
+                    .filter(|(_, values)| values.is_valid(row))
+                    .map(|(_, values)| values.value(row))
+                    .filter(|value| !value.is_nan())
+            })
+        });
+        Ok(Float64Array::from_iter_values(scores))
+    }
+}
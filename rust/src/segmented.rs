@@ -0,0 +1,203 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Contextual anomaly detection: one forest per segment of the data, such as a host, customer or region, so that
+//! each sample is judged against its peers, with a global forest for segments too sparse to have their own.
+
+extern crate serde;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use self::serde::{Serialize, Deserialize};
+use crate::builder::ForestBuilder;
+use crate::isolation_forest::{Forest, ForestError, Sample};
+
+/// A forest along with the number of samples it was given.
+#[derive(Serialize, Deserialize)]
+struct Segment {
+    #[serde(rename = "Num Samples")]
+    num_samples: usize, // All of the samples added to this segment, including those added before it was saved
+    #[serde(skip)]
+    num_new_samples: usize, // Samples added since the segment's trees were last built
+    #[serde(skip)]
+    num_held_samples: usize, // Samples the forest holds, which excludes those added before the segment was saved
+    #[serde(rename = "Forest")]
+    forest: Forest,
+}
+
+impl Segment {
+    fn new(options: &ForestBuilder) -> Segment {
+        let forest = options.clone().build().expect("The options were validated when the segmented forest was created or loaded");
+        Segment { num_samples: 0, num_new_samples: 0, num_held_samples: 0, forest }
+    }
+
+    fn add_sample(&mut self, sample: Sample) {
+        self.num_samples += 1;
+        self.num_new_samples += 1;
+        self.num_held_samples += 1;
+        self.forest.add_sample(sample);
+    }
+
+    fn create(&mut self) {
+        if self.num_new_samples == 0 {
+            return;
+        }
+
+        // The samples of a loaded segment weren't saved, so rather than rebuilding its trees from the new samples
+        // alone, replace a share of the trees, in proportion to the new samples, with trees built from them.
+        let num_trees = self.forest.num_trees();
+        if num_trees > 0 && self.num_held_samples < self.num_samples {
+            let num_new_trees = (num_trees * self.num_new_samples).div_ceil(self.num_samples);
+            self.forest.grow(num_new_trees as u32);
+            self.forest.prune_oldest(self.forest.num_trees() - num_trees);
+        }
+        else {
+            self.forest.create();
+        }
+        self.num_new_samples = 0;
+    }
+}
+
+/// Trains and scores a forest per segment. Every sample also trains the global forest, which scores the samples of
+/// segments with fewer than the minimum number of samples.
+#[derive(Serialize, Deserialize)]
+pub struct SegmentedForest {
+    #[serde(rename = "Options")]
+    options: ForestBuilder, // Used to build the forest of each new segment
+    #[serde(rename = "Min Segment Samples")]
+    min_segment_samples: usize, // Segments with fewer samples are scored by the global forest
+    #[serde(rename = "Global")]
+    global: Segment,
+    #[serde(rename = "Segments")]
+    segments: BTreeMap<String, Segment>,
+}
+
+impl SegmentedForest {
+    /// Creates an empty segmented forest whose forests are all built with the given options.
+    pub fn new(options: ForestBuilder, min_segment_samples: usize) -> Result<SegmentedForest, ForestError> {
+        options.clone().build()?;
+        let global = Segment::new(&options);
+        Ok(SegmentedForest { options, min_segment_samples, global, segments: BTreeMap::new() })
+    }
+
+    /// Adds a training sample to its segment and to the global forest.
+    pub fn add_sample(&mut self, segment_name: &str, sample: Sample) {
+        let options = &self.options;
+        let segment = self.segments.entry(segment_name.to_string()).or_insert_with(|| Segment::new(options));
+        segment.add_sample(sample.clone());
+        self.global.add_sample(sample);
+    }
+
+    /// Builds the trees of the global forest and of each segment with enough samples. Only forests that were given
+    /// samples since they were last built are rebuilt. A loaded segmented forest can be trained further: as the
+    /// samples it was trained on are not saved, each loaded forest keeps its number of trees and replaces the oldest
+    /// of them, in proportion to the share of its samples that are new, with trees built from the new samples.
+    pub fn create(&mut self) {
+        self.global.create();
+        for segment in self.segments.values_mut() {
+            if segment.num_samples >= self.min_segment_samples {
+                segment.create();
+            }
+        }
+    }
+
+    /// Returns the number of segments that have been given samples.
+    pub fn num_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns the name of each segment, in sorted order.
+    pub fn segment_names(&self) -> impl Iterator<Item = &str> {
+        self.segments.keys().map(|segment_name| segment_name.as_str())
+    }
+
+    /// Returns true if the segment's samples are scored by the global forest, because the segment is unknown, has
+    /// too few samples, or has not been built.
+    pub fn uses_global(&self, segment_name: &str) -> bool {
+        self.segments.get(segment_name).is_none_or(|segment| segment.num_samples < self.min_segment_samples || segment.forest.num_trees() == 0)
+    }
+
+    /// Returns the forest that scores the segment's samples.
+    pub fn forest(&self, segment_name: &str) -> &Forest {
+        if self.uses_global(segment_name) {
+            &self.global.forest
+        }
+        else {
+            &self.segments[segment_name].forest
+        }
+    }
+
+    /// Returns the global forest.
+    pub fn global_forest(&self) -> &Forest {
+        &self.global.forest
+    }
+
+    /// Scores the sample against its segment's forest.
+    pub fn score(&self, segment_name: &str, sample: &Sample) -> f64 {
+        self.forest(segment_name).score(sample)
+    }
+
+    /// Scores the sample against its segment's forest, normalized to between 0 and 1.
+    pub fn normalized_score(&self, segment_name: &str, sample: &Sample) -> f64 {
+        self.forest(segment_name).normalized_score(sample)
+    }
+
+    /// Returns true if the sample is an anomaly for its segment's forest.
+    pub fn is_anomaly(&self, segment_name: &str, sample: &Sample) -> bool {
+        self.forest(segment_name).is_anomaly(sample)
+    }
+
+    /// Serializes the options, the global forest and every segment to JSON.
+    pub fn dump(&self) -> String {
+        serde_json::to_string(&self).unwrap()
+    }
+
+    /// Creates a segmented forest from JSON produced by `dump`. Returns an error if the saved options are invalid.
+    pub fn load(json_str: &str) -> Result<SegmentedForest, ForestError> {
+        let segmented_forest = serde_json::from_str(json_str).map_err(|e| ForestError::ParseError(e.to_string()))?;
+        SegmentedForest::restored(segmented_forest)
+    }
+
+    /// Writes the same JSON as `dump`, without first building it in memory.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), ForestError> {
+        serde_json::to_writer(writer, &self).map_err(|e| ForestError::WriteError(e.to_string()))
+    }
+
+    /// Reads a segmented forest written by `write` or `dump`. Returns an error if the saved options are invalid.
+    pub fn read<R: Read>(reader: R) -> Result<SegmentedForest, ForestError> {
+        let segmented_forest = serde_json::from_reader(reader).map_err(|e| ForestError::ParseError(e.to_string()))?;
+        SegmentedForest::restored(segmented_forest)
+    }
+
+    /// Checks the options of a loaded segmented forest, as they are used to build new segments, and restores the
+    /// random number generators of its forests.
+    fn restored(mut segmented_forest: SegmentedForest) -> Result<SegmentedForest, ForestError> {
+        segmented_forest.options.clone().build()?;
+        segmented_forest.global.forest.restore_seed();
+        for segment in segmented_forest.segments.values_mut() {
+            segment.forest.restore_seed();
+        }
+        Ok(segmented_forest)
+    }
+}
@@ -0,0 +1,366 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Rules for choosing how each tree node splits the training data that reaches it. A `Forest` uses a `ValueUniform`
+//! splitter unless another is given to `Forest::set_splitter`; any type implementing `Splitter` can be used.
+
+extern crate rand;
+
+use rand::{Rng, RngCore};
+use crate::isolation_forest::Float64Vec;
+use crate::training_data::TrainingData;
+
+/// A split chosen for a tree node. Samples whose value is less than the split value go left, the rest go right.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Split {
+    Feature { feature_name: String, split_value: f64 }, // Compare a single feature with the split value
+    Hyperplane { terms: Vec<(String, f64)>, split_value: f64 }, // Compare a weighted sum of features with the split value
+}
+
+impl Split {
+    /// Returns the value that samples are compared with.
+    pub fn split_value(&self) -> f64 {
+        match self {
+            Split::Feature { split_value, .. } | Split::Hyperplane { split_value, .. } => *split_value,
+        }
+    }
+
+    /// Returns the same split at a different value.
+    fn with_split_value(self, split_value: f64) -> Split {
+        match self {
+            Split::Feature { feature_name, .. } => Split::Feature { feature_name, split_value },
+            Split::Hyperplane { terms, .. } => Split::Hyperplane { terms, split_value },
+        }
+    }
+}
+
+/// The training data that reached a tree node.
+pub struct NodeData<'a> {
+    data: &'a TrainingData,
+    rows: &'a [usize], // Rows of the training data that reached the node
+    feature_names: &'a [String],
+    depth: u32,
+}
+
+impl<'a> NodeData<'a> {
+    pub(crate) fn new(data: &'a TrainingData, rows: &'a [usize], feature_names: &'a [String], depth: u32) -> NodeData<'a> {
+        NodeData { data, rows, feature_names, depth }
+    }
+
+    /// Returns the number of training samples that reached the node.
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the depth of the node, where the root is at depth zero.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Returns the names of the features the tree may split on, sorted.
+    pub fn feature_names(&self) -> &[String] {
+        self.feature_names
+    }
+
+    /// Returns the value of the feature for one of the node's samples, or None if the sample doesn't have the feature.
+    pub fn value(&self, feature_name: &str, i: usize) -> Option<f64> {
+        self.data.value(feature_name, self.rows[i])
+    }
+
+    /// Returns the values of the feature for the node's samples, leaving out samples that don't have the feature.
+    pub fn values(&self, feature_name: &str) -> Float64Vec {
+        match self.data.column(feature_name) {
+            Some(column) => self.rows.iter().map(|&row| column[row]).filter(|value| !value.is_nan()).collect(),
+            None => Float64Vec::new(),
+        }
+    }
+
+    /// Returns the mean of the feature over the node's samples, or None if none of them have the feature.
+    pub fn mean(&self, feature_name: &str) -> Option<f64> {
+        let values = self.values(feature_name);
+        if values.is_empty() { None } else { Some(values.iter().sum::<f64>() / values.len() as f64) }
+    }
+
+    /// Returns the value the split compares with its split value for each of the node's samples. Missing values are
+    /// replaced with the feature's mean over the node's samples.
+    pub fn split_values(&self, split: &Split) -> Float64Vec {
+        let terms: Vec<(&str, f64)> = match split {
+            Split::Feature { feature_name, .. } => vec![(feature_name.as_str(), 1.0)],
+            Split::Hyperplane { terms, .. } => terms.iter().map(|(feature_name, coefficient)| (feature_name.as_str(), *coefficient)).collect(),
+        };
+        let columns: Vec<(&[f64], f64, f64)> = terms.into_iter().map(|(feature_name, coefficient)| {
+            (self.data.column(feature_name).unwrap_or(&[]), coefficient, self.mean(feature_name).unwrap_or(f64::NAN))
+        }).collect();
+
+        self.rows.iter().map(|&row| {
+            columns.iter().map(|(column, coefficient, mean)| {
+                let value = column.get(row).copied().filter(|value| !value.is_nan()).unwrap_or(*mean);
+                coefficient * value
+            }).sum()
+        }).collect()
+    }
+
+    /// Divides the node's rows between the two sides of the split, and returns them along with the range of the
+    /// values the split compared.
+    pub(crate) fn partition(&self, split: &Split) -> (Vec<usize>, Vec<usize>, (f64, f64)) {
+        let split_value = split.split_value();
+        let mut left_rows = Vec::new();
+        let mut right_rows = Vec::new();
+        let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+        for (&row, value) in self.rows.iter().zip(self.split_values(split)) {
+            if value < split_value { left_rows.push(row) } else { right_rows.push(row) }
+            range = (range.0.min(value), range.1.max(value));
+        }
+        (left_rows, right_rows, range)
+    }
+}
+
+/// Chooses how to split the training data that reached a tree node.
+pub trait Splitter: Send + Sync {
+    /// Returns the split to use at the node, or None to make the node a leaf. Random choices should be drawn from
+    /// the given generator, so that forests built with a seed can be reproduced.
+    fn split(&self, node: &NodeData, rng: &mut dyn RngCore) -> Option<Split>;
+
+    /// Returns true if samples whose value falls outside the range of the node's training data should be isolated
+    /// at the node. Splitters that place splits where the data is dense need this to isolate anomalies that are
+    /// beyond the training data.
+    fn range_check(&self) -> bool {
+        false
+    }
+
+    /// Returns true if samples whose value falls outside the range of the tree's training data should be isolated
+    /// at the first node that splits on the feature. Unlike `range_check`, this never isolates samples that fall in
+    /// the gaps between training values, so it doesn't raise the scores of typical samples.
+    fn tree_range_check(&self) -> bool {
+        false
+    }
+}
+
+/// Selects a feature at random and splits at one of its values, chosen uniformly. This is the forest's default.
+/// Samples outside the range of the tree's training data are isolated at the first node that splits on the feature,
+/// as otherwise they would follow the path of the most extreme training value.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ValueUniform;
+
+impl Splitter for ValueUniform {
+    fn split(&self, node: &NodeData, rng: &mut dyn RngCore) -> Option<Split> {
+        let feature_names = node.feature_names();
+        if feature_names.is_empty() {
+            return None;
+        }
+
+        let feature_name = &feature_names[rng.random_range(0..feature_names.len())];
+        let values = node.values(feature_name);
+        if values.len() <= 1 || values.iter().all(|value| *value == values[0]) {
+            return None;
+        }
+
+        let split_value = values[rng.random_range(0..values.len())];
+        Some(Split::Feature { feature_name: feature_name.clone(), split_value })
+    }
+
+    fn tree_range_check(&self) -> bool {
+        true
+    }
+}
+
+/// Selects a feature at random and splits at a value chosen uniformly between its minimum and maximum, as in the
+/// original paper.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RangeUniform;
+
+impl Splitter for RangeUniform {
+    fn split(&self, node: &NodeData, rng: &mut dyn RngCore) -> Option<Split> {
+        let feature_names = node.feature_names();
+        if feature_names.is_empty() {
+            return None;
+        }
+
+        let feature_name = &feature_names[rng.random_range(0..feature_names.len())];
+        let (min, max) = range(&node.values(feature_name))?;
+        Some(Split::Feature { feature_name: feature_name.clone(), split_value: rng.random_range(min..max) })
+    }
+}
+
+/// Splits on a random hyperplane through a random point within the range of the node's data, as in Extended
+/// Isolation Forest (Hariri, Kind and Brunner, 2018). The hyperplane spans `extension_level + 1` randomly selected
+/// features, so an extension level of zero gives axis-parallel splits.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExtendedHyperplane {
+    pub extension_level: u32,
+}
+
+impl Splitter for ExtendedHyperplane {
+    fn split(&self, node: &NodeData, rng: &mut dyn RngCore) -> Option<Split> {
+        let feature_names = node.feature_names();
+        let num_features = (self.extension_level as usize + 1).min(feature_names.len());
+        let mut terms = Vec::with_capacity(num_features);
+        let mut split_value = 0.0;
+
+        for i in rand::seq::index::sample(rng, feature_names.len(), num_features) {
+            if let Some((min, max)) = range(&node.values(&feature_names[i])) {
+                let coefficient = standard_normal(rng);
+                split_value += coefficient * rng.random_range(min..max);
+                terms.push((feature_names[i].clone(), coefficient));
+            }
+        }
+        if terms.is_empty() {
+            return None;
+        }
+        Some(Split::Hyperplane { terms, split_value })
+    }
+}
+
+/// Keeps the best of several random hyperplanes, each over up to `num_features` randomly selected features, as in
+/// SCiForest (Liu, Ting and Zhou, 2010). Each hyperplane is split where it most reduces the average standard
+/// deviation of the two sides, which is better at isolating clustered anomalies. Samples outside the range of a
+/// node's training data are isolated at that node.
+#[derive(Clone, Copy, Debug)]
+pub struct SCiForest {
+    pub num_hyperplanes: u32,
+    pub num_features: u32,
+}
+
+impl Splitter for SCiForest {
+    fn split(&self, node: &NodeData, rng: &mut dyn RngCore) -> Option<Split> {
+        best_hyperplane(node, rng, self.num_hyperplanes, self.num_features, |_, std, left, right| {
+            (std - (left.std + right.std) / 2.0) / std
+        })
+    }
+
+    fn range_check(&self) -> bool {
+        true
+    }
+}
+
+/// Like `SCiForest`, but weighs the standard deviation of each side by its size, as in Fair-Cut Forest (Cortes,
+/// 2021), which avoids favoring splits that cut off a single sample.
+#[derive(Clone, Copy, Debug)]
+pub struct FairCut {
+    pub num_hyperplanes: u32,
+    pub num_features: u32,
+}
+
+impl Splitter for FairCut {
+    fn split(&self, node: &NodeData, rng: &mut dyn RngCore) -> Option<Split> {
+        best_hyperplane(node, rng, self.num_hyperplanes, self.num_features, |n, std, left, right| {
+            (std - (left.n * left.std + right.n * right.std) / n) / std
+        })
+    }
+
+    fn range_check(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the minimum and maximum of the values, or None if there are fewer than two distinct values.
+fn range(values: &[f64]) -> Option<(f64, f64)> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if min < max { Some((min, max)) } else { None }
+}
+
+/// Draws from the standard normal distribution, using the Box-Muller transform.
+fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Size and standard deviation of one side of a candidate split.
+struct Side {
+    n: f64,
+    std: f64,
+}
+
+impl Side {
+    fn from_sums(n: f64, sum: f64, sum_of_squares: f64) -> Side {
+        let mean = sum / n;
+        Side { n, std: (sum_of_squares / n - mean * mean).max(0.0).sqrt() }
+    }
+}
+
+/// Finds the split of the sorted projections with the largest gain, returning the gain and the split value. Returns
+/// None if every projection is the same. The gain is computed from the number of projections, their standard
+/// deviation, and the two sides of the split.
+fn best_split_of<G: Fn(f64, f64, &Side, &Side) -> f64>(sorted: &[f64], gain: &G) -> Option<(f64, f64)> {
+    let n = sorted.len() as f64;
+    let sum: f64 = sorted.iter().sum();
+    let sum_of_squares: f64 = sorted.iter().map(|value| value * value).sum();
+    let std = Side::from_sums(n, sum, sum_of_squares).std;
+    if std <= 0.0 {
+        return None;
+    }
+
+    let mut best: Option<(f64, f64)> = None;
+    let mut left_sum = 0.0;
+    let mut left_sum_of_squares = 0.0;
+    for i in 1..sorted.len() {
+        left_sum += sorted[i - 1];
+        left_sum_of_squares += sorted[i - 1] * sorted[i - 1];
+        if sorted[i - 1] == sorted[i] {
+            continue;
+        }
+
+        let left = Side::from_sums(i as f64, left_sum, left_sum_of_squares);
+        let right = Side::from_sums(n - i as f64, sum - left_sum, sum_of_squares - left_sum_of_squares);
+        let split_gain = gain(n, std, &left, &right);
+        if best.is_none_or(|(best_gain, _)| split_gain > best_gain) {
+            best = Some((split_gain, (sorted[i - 1] + sorted[i]) / 2.0));
+        }
+    }
+    best
+}
+
+/// Tries the given number of random hyperplanes, each over up to `num_features` randomly selected features, and
+/// returns the split with the largest gain. Returns None if none of the hyperplanes can separate the node's data.
+fn best_hyperplane<G: Fn(f64, f64, &Side, &Side) -> f64>(node: &NodeData, rng: &mut dyn RngCore, num_hyperplanes: u32, num_features: u32, gain: G) -> Option<Split> {
+    let feature_names = node.feature_names();
+    let num_features = (num_features as usize).min(feature_names.len());
+    let mut best: Option<(f64, Split)> = None;
+
+    for _ in 0..num_hyperplanes {
+        let mut terms = Vec::with_capacity(num_features);
+        for i in rand::seq::index::sample(rng, feature_names.len(), num_features) {
+            // Scale each coefficient by the feature's spread so that no feature dominates because of its units.
+            let values = node.values(&feature_names[i]);
+            let std = Side::from_sums(values.len() as f64, values.iter().sum(), values.iter().map(|value| value * value).sum()).std;
+            if std > 0.0 {
+                terms.push((feature_names[i].clone(), rng.random_range(-1.0..=1.0) / std));
+            }
+        }
+        if terms.is_empty() {
+            continue;
+        }
+
+        let hyperplane = Split::Hyperplane { terms, split_value: 0.0 };
+        let mut projections = node.split_values(&hyperplane);
+        projections.sort_unstable_by(f64::total_cmp);
+        if let Some((split_gain, split_value)) = best_split_of(&projections, &gain) {
+            if best.as_ref().is_none_or(|(best_gain, _)| split_gain > *best_gain) {
+                best = Some((split_gain, hyperplane.with_split_value(split_value)));
+            }
+        }
+    }
+    best.map(|(_, split)| split)
+}
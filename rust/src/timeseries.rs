@@ -0,0 +1,148 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Anomaly detection for univariate time series. Each point is turned into a `Sample` describing the window of
+//! points that ends with it, so that a `Forest` can find points that are unusual given what came before them.
+
+use crate::isolation_forest::{Feature, FeatureList, Forest, Sample};
+
+pub const FEATURE_VALUE: &str = "value";
+pub const FEATURE_DELTA: &str = "delta";
+pub const FEATURE_INTERVAL: &str = "interval";
+pub const FEATURE_ROLLING_MEAN: &str = "rolling mean";
+pub const FEATURE_ROLLING_STD: &str = "rolling std";
+
+/// A single observation in a time series.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub timestamp: f64,
+    pub value: f64,
+}
+
+impl Point {
+    pub fn new(timestamp: f64, value: f64) -> Point {
+        Point { timestamp, value }
+    }
+}
+
+/// Describes how each point is turned into a window of features.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub window_size: usize, // Number of points in each window, including the point being described
+    pub num_lags: usize, // Number of previous values to include as features, at most window_size - 1
+    pub max_gap: Option<f64>, // Points further apart than this start a new window; defaults to three times the median interval
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig { window_size: 8, num_lags: 3, max_gap: None }
+    }
+}
+
+/// Returns the name of the feature holding the value from the given number of points earlier.
+pub fn lag_feature_name(lag: usize) -> String {
+    format!("lag {}", lag)
+}
+
+fn median_interval(points: &[(usize, Point)]) -> Option<f64> {
+    let mut intervals: Vec<f64> = points.windows(2).map(|pair| pair[1].1.timestamp - pair[0].1.timestamp).collect();
+    if intervals.is_empty() {
+        return None;
+    }
+    intervals.sort_unstable_by(f64::total_cmp);
+    Some(intervals[intervals.len() / 2])
+}
+
+fn window_sample(window: &[Point], num_lags: usize) -> Sample {
+    let current = window[window.len() - 1];
+    let previous = window[window.len() - 2];
+    let mean = window.iter().map(|point| point.value).sum::<f64>() / window.len() as f64;
+    let variance = window.iter().map(|point| (point.value - mean).powi(2)).sum::<f64>() / window.len() as f64;
+
+    let mut features: FeatureList = vec![
+        Feature::new(FEATURE_VALUE, current.value),
+        Feature::new(FEATURE_DELTA, current.value - previous.value),
+        Feature::new(FEATURE_INTERVAL, current.timestamp - previous.timestamp),
+        Feature::new(FEATURE_ROLLING_MEAN, mean),
+        Feature::new(FEATURE_ROLLING_STD, variance.sqrt()),
+    ];
+    for lag in 1..=num_lags {
+        features.push(Feature::new(&lag_feature_name(lag), window[window.len() - 1 - lag].value));
+    }
+
+    let mut sample = Sample::new(&current.timestamp.to_string());
+    sample.add_features(&mut features);
+    sample
+}
+
+/// Turns each point of the series into a sample describing the window of points that ends with it. The result is
+/// aligned with the series. Points do not need to be in order or evenly spaced. A point has no sample if its value
+/// is not finite or if there are not enough points before it to fill a window, either because it is near the start
+/// of the series or because it comes shortly after a gap or a missing value.
+pub fn windowed_samples(series: &[Point], config: &WindowConfig) -> Vec<Option<Sample>> {
+    let window_size = config.window_size.max(2).max(config.num_lags + 1);
+    let mut samples = vec![None; series.len()];
+
+    let mut points: Vec<(usize, Point)> = series.iter().copied().enumerate().filter(|(_, point)| point.timestamp.is_finite()).collect();
+    points.sort_by(|a, b| a.1.timestamp.total_cmp(&b.1.timestamp));
+    let max_gap = config.max_gap.or_else(|| median_interval(&points).map(|interval| 3.0 * interval)).unwrap_or(f64::INFINITY);
+
+    let mut window: Vec<Point> = Vec::with_capacity(window_size);
+    let mut last_timestamp = f64::NEG_INFINITY;
+    for (index, point) in points {
+        if !point.value.is_finite() || point.timestamp - last_timestamp > max_gap {
+            window.clear();
+        }
+        last_timestamp = point.timestamp;
+        if !point.value.is_finite() {
+            continue;
+        }
+
+        if window.len() == window_size {
+            window.remove(0);
+        }
+        window.push(point);
+        if window.len() == window_size {
+            samples[index] = Some(window_sample(&window, config.num_lags));
+        }
+    }
+    samples
+}
+
+/// Scores each point of the series against an already trained forest. The result is aligned with the series; points
+/// without a window, as described by `windowed_samples`, have no score.
+pub fn score_series(forest: &Forest, series: &[Point], config: &WindowConfig) -> Vec<Option<f64>> {
+    windowed_samples(series, config).iter().map(|sample| sample.as_ref().map(|sample| forest.normalized_score(sample))).collect()
+}
+
+/// Trains the forest on the windows of the series, then scores each point. The result is aligned with the series;
+/// points without a window, as described by `windowed_samples`, have no score.
+pub fn fit_score(forest: &mut Forest, series: &[Point], config: &WindowConfig) -> Vec<Option<f64>> {
+    let samples = windowed_samples(series, config);
+    for sample in samples.iter().flatten() {
+        forest.add_sample(sample.clone());
+    }
+    forest.create();
+
+    samples.iter().map(|sample| sample.as_ref().map(|sample| forest.normalized_score(sample))).collect()
+}
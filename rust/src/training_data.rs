@@ -0,0 +1,163 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Storage for a forest's training data.
+
+extern crate rand;
+
+use rand::Rng;
+use rand::distr::{Distribution as _, weighted::WeightedIndex};
+use std::collections::HashMap;
+use crate::isolation_forest::{Float64Vec, FeatureNameToValuesMap, Sample};
+
+/// Training data, stored by column so that rows can be drawn without keeping `Sample`s around. Every column has a
+/// value for every row, with NaN marking rows that don't have the feature.
+#[derive(Clone, Default)]
+pub(crate) struct TrainingData {
+    columns: HashMap<String, Float64Vec>,
+    weights: Float64Vec, // One weight per row
+    weighted: bool, // True if any row was given a weight
+    labels: Vec<Option<bool>>, // One label per row, true for known anomalies, or None if the row is unlabeled
+    num_rows: usize,
+}
+
+impl TrainingData {
+    pub(crate) fn new() -> TrainingData {
+        TrainingData::default()
+    }
+
+    /// Creates training data from columns that all have the same number of rows.
+    pub(crate) fn from_columns(columns: HashMap<String, Float64Vec>) -> TrainingData {
+        let num_rows = columns.values().map(|column| column.len()).max().unwrap_or(0);
+        debug_assert!(columns.values().all(|column| column.len() == num_rows));
+        TrainingData { columns, weights: vec![1.0; num_rows], weighted: false, labels: vec![None; num_rows], num_rows }
+    }
+
+    pub(crate) fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    /// Returns the feature names, sorted.
+    pub(crate) fn feature_names(&self) -> Vec<String> {
+        let mut feature_names: Vec<String> = self.columns.keys().cloned().collect();
+        feature_names.sort();
+        feature_names
+    }
+
+    /// Adds the sample as a new row.
+    pub(crate) fn push_sample(&mut self, sample: &Sample) {
+        for (feature_name, value) in sample.feature_values() {
+            let num_rows = self.num_rows;
+            let column = self.columns.entry(feature_name.to_string()).or_insert_with(|| vec![f64::NAN; num_rows]);
+            if column.len() == num_rows {
+                column.push(value);
+            }
+        }
+        self.num_rows += 1;
+        for column in self.columns.values_mut() {
+            column.resize(self.num_rows, f64::NAN);
+        }
+
+        self.weights.push(sample.weight());
+        self.weighted |= sample.has_weight();
+        self.labels.push(sample.label());
+    }
+
+    /// Returns the row's label, true if it is a known anomaly, or None if it is unlabeled.
+    pub(crate) fn label(&self, row: usize) -> Option<bool> {
+        self.labels[row]
+    }
+
+    /// Returns the feature's column, with NaN for rows that don't have the feature.
+    pub(crate) fn column(&self, feature_name: &str) -> Option<&[f64]> {
+        self.columns.get(feature_name).map(|column| column.as_slice())
+    }
+
+    /// Returns the value of the feature for the given row, or None if the row doesn't have the feature.
+    pub(crate) fn value(&self, feature_name: &str, row: usize) -> Option<f64> {
+        self.columns.get(feature_name).map(|column| column[row]).filter(|value| !value.is_nan())
+    }
+
+    /// Collects the sorted feature values of the given rows.
+    pub(crate) fn feature_values(&self, rows: &[usize]) -> FeatureNameToValuesMap {
+        let mut feature_values = FeatureNameToValuesMap::new();
+        for (feature_name, column) in &self.columns {
+            let mut values: Float64Vec = rows.iter().map(|&row| column[row]).filter(|value| !value.is_nan()).collect();
+            if !values.is_empty() {
+                values.sort_unstable_by(f64::total_cmp);
+                feature_values.insert(feature_name.clone(), values);
+            }
+        }
+        feature_values
+    }
+
+    /// Draws the given number of the rows to build a tree from. Weighted rows are drawn with replacement, in
+    /// proportion to their weights; otherwise rows are drawn with replacement only if asked to, and all of the
+    /// rows are used if there aren't more than the number asked for.
+    pub(crate) fn draw_rows<R: Rng + ?Sized>(&self, rows: &[usize], num_rows: usize, with_replacement: bool, rng: &mut R) -> Vec<usize> {
+        if self.weighted {
+            if let Ok(distribution) = WeightedIndex::new(rows.iter().map(|&row| self.weights[row])) {
+                return (0..num_rows).map(|_| rows[distribution.sample(rng)]).collect();
+            }
+        }
+
+        if rows.is_empty() {
+            Vec::new()
+        }
+        else if with_replacement {
+            (0..num_rows).map(|_| rows[rng.random_range(0..rows.len())]).collect()
+        }
+        else if num_rows < rows.len() {
+            rand::seq::index::sample(rng, rows.len(), num_rows).into_iter().map(|i| rows[i]).collect()
+        }
+        else {
+            rows.to_vec()
+        }
+    }
+
+    /// Splits the training data in two at the given row, returning the rows from that point on.
+    pub(crate) fn split_off(&mut self, at: usize) -> TrainingData {
+        let columns = self.columns.iter_mut().map(|(feature_name, column)| (feature_name.clone(), column.split_off(at))).collect();
+        let weights = self.weights.split_off(at);
+        let labels = self.labels.split_off(at);
+        let num_rows = self.num_rows - at;
+        self.num_rows = at;
+        TrainingData { columns, weights, weighted: self.weighted, labels, num_rows }
+    }
+
+    /// Adds the rows of the other training data after these rows.
+    pub(crate) fn append(&mut self, other: TrainingData) {
+        for (feature_name, mut column) in other.columns {
+            let num_rows = self.num_rows;
+            self.columns.entry(feature_name).or_insert_with(|| vec![f64::NAN; num_rows]).append(&mut column);
+        }
+        self.num_rows += other.num_rows;
+        for column in self.columns.values_mut() {
+            column.resize(self.num_rows, f64::NAN);
+        }
+
+        self.weights.extend(other.weights);
+        self.weighted |= other.weighted;
+        self.labels.extend(other.labels);
+    }
+}
@@ -0,0 +1,199 @@
+//	MIT License
+//
+//  Copyright © 2018 Michael J Simms. All rights reserved.
+//
+//	Permission is hereby granted, free of charge, to any person obtaining a copy
+//	of this software and associated documentation files (the "Software"), to deal
+//	in the Software without restriction, including without limitation the rights
+//	to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//	copies of the Software, and to permit persons to whom the Software is
+//	furnished to do so, subject to the following conditions:
+//
+//	The above copyright notice and this permission notice shall be included in all
+//	copies or substantial portions of the Software.
+//
+//	THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//	IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//	FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//	AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//	LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//	OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//	SOFTWARE.
+
+
+//! Searches for the number of trees, subsample size and depth that give the best forest, as judged by a labeled
+//! validation set or any other criterion.
+
+extern crate rand;
+extern crate serde;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use self::serde::Serialize;
+use crate::isolation_forest::{Forest, ForestError, MaxSamples, Sample};
+
+/// One combination of the options being searched.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Configuration {
+    pub num_trees: u32,
+    pub max_samples: MaxSamples,
+    pub max_depth: u32, // Zero for no limit
+}
+
+/// The values to try for each option.
+#[derive(Clone, Debug)]
+pub struct SearchSpace {
+    pub num_trees: Vec<u32>,
+    pub max_samples: Vec<MaxSamples>,
+    pub max_depth: Vec<u32>,
+}
+
+impl SearchSpace {
+    /// Returns every combination of the values.
+    pub fn grid(&self) -> Vec<Configuration> {
+        let mut configurations = Vec::new();
+        for &num_trees in &self.num_trees {
+            for &max_samples in &self.max_samples {
+                for &max_depth in &self.max_depth {
+                    configurations.push(Configuration { num_trees, max_samples, max_depth });
+                }
+            }
+        }
+        configurations
+    }
+}
+
+/// How the search space is explored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Search {
+    Grid, // Try every combination
+    Random { num_configurations: usize }, // Try this many distinct combinations, chosen at random
+}
+
+/// Judges a trained forest. Higher values are better.
+pub trait Criterion {
+    fn evaluate(&self, forest: &Forest) -> f64;
+}
+
+impl<F: Fn(&Forest) -> f64> Criterion for F {
+    fn evaluate(&self, forest: &Forest) -> f64 {
+        self(forest)
+    }
+}
+
+/// Judges a forest by the area under the ROC curve of its normalized scores on labeled validation samples.
+pub struct LabeledAuc<'a> {
+    samples: &'a [Sample],
+    labels: &'a [bool], // True for each sample that is an anomaly
+}
+
+impl<'a> LabeledAuc<'a> {
+    pub fn new(samples: &'a [Sample], labels: &'a [bool]) -> LabeledAuc<'a> {
+        assert_eq!(samples.len(), labels.len(), "Each validation sample needs a label");
+        LabeledAuc { samples, labels }
+    }
+}
+
+impl Criterion for LabeledAuc<'_> {
+    fn evaluate(&self, forest: &Forest) -> f64 {
+        let scores: Vec<f64> = self.samples.iter().map(|sample| forest.normalized_score(sample)).collect();
+        roc_auc(&scores, self.labels)
+    }
+}
+
+/// Returns the area under the ROC curve, which is the probability that a randomly chosen anomaly scores higher than
+/// a randomly chosen normal sample, counting ties as half. Returns 0.5 if either class is empty.
+pub fn roc_auc(scores: &[f64], labels: &[bool]) -> f64 {
+    let mut ranked: Vec<(f64, bool)> = scores.iter().copied().zip(labels.iter().copied()).collect();
+    ranked.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Sum the ranks of the anomalies, giving tied scores their average rank.
+    let mut anomaly_rank_sum = 0.0;
+    let mut i = 0;
+    while i < ranked.len() {
+        let mut j = i;
+        while j < ranked.len() && ranked[j].0 == ranked[i].0 {
+            j += 1;
+        }
+        let average_rank = (i + j + 1) as f64 / 2.0;
+        anomaly_rank_sum += average_rank * ranked[i..j].iter().filter(|(_, label)| *label).count() as f64;
+        i = j;
+    }
+
+    let num_anomalies = labels.iter().filter(|label| **label).count() as f64;
+    let num_normal = labels.len() as f64 - num_anomalies;
+    if num_anomalies == 0.0 || num_normal == 0.0 {
+        return 0.5;
+    }
+    (anomaly_rank_sum - num_anomalies * (num_anomalies + 1.0) / 2.0) / (num_anomalies * num_normal)
+}
+
+/// The result of training and judging a forest with one configuration. Configurations that the forest rejects, such
+/// as a depth the subsample size can't reach, are recorded as failed trials rather than ending the search.
+#[derive(Clone, Debug, Serialize)]
+pub struct Trial {
+    pub configuration: Configuration,
+    pub score: Option<f64>, // None if the configuration is invalid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // Why the configuration is invalid
+}
+
+/// The best configuration found, along with every configuration that was tried, in the order they were tried.
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchResults {
+    pub best: Configuration,
+    pub best_score: f64,
+    pub trials: Vec<Trial>,
+}
+
+/// Trains a forest on the training samples for each configuration chosen from the search space, judges it with the
+/// criterion, and returns the results. Giving a seed makes the search and the forests reproducible. Returns an error if
+/// none of the configurations are valid.
+pub fn search<C: Criterion + ?Sized>(training_samples: &[Sample], space: &SearchSpace, search: Search, criterion: &C, seed: Option<u64>) -> Result<SearchResults, ForestError> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
+
+    let mut configurations = space.grid();
+    if let Search::Random { num_configurations } = search {
+        let num_configurations = num_configurations.min(configurations.len());
+        let indexes = rand::seq::index::sample(&mut rng, configurations.len(), num_configurations);
+        configurations = indexes.into_iter().map(|i| configurations[i]).collect();
+    }
+    if configurations.is_empty() {
+        return Err(ForestError::InvalidParameter("the search space is empty".to_string()));
+    }
+
+    let mut trials = Vec::with_capacity(configurations.len());
+    for configuration in configurations {
+        let mut builder = Forest::builder()
+            .num_trees(configuration.num_trees)
+            .max_samples(configuration.max_samples)
+            .max_depth(configuration.max_depth);
+        if seed.is_some() {
+            builder = builder.seed(rand::Rng::random(&mut rng));
+        }
+
+        let mut forest = match builder.build() {
+            Ok(forest) => forest,
+            Err(error) => {
+                trials.push(Trial { configuration, score: None, error: Some(error.to_string()) });
+                continue;
+            }
+        };
+        for sample in training_samples {
+            forest.add_sample(sample.clone());
+        }
+        forest.create();
+        trials.push(Trial { configuration, score: Some(criterion.evaluate(&forest)), error: None });
+    }
+
+    let best_trial = trials.iter()
+        .filter_map(|trial| trial.score.map(|score| (trial.configuration, score)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    match best_trial {
+        Some((best, best_score)) => Ok(SearchResults { best, best_score, trials }),
+        None => Err(ForestError::InvalidParameter("none of the configurations in the search space are valid".to_string())),
+    }
+}